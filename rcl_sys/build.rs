@@ -19,12 +19,17 @@ fn main() {
     }
 
     println!("cargo:rustc-link-lib=dylib=rcl");
+    println!("cargo:rustc-link-lib=dylib=rcl_lifecycle");
+    println!("cargo:rustc-link-lib=dylib=rcl_yaml_param_parser");
     println!("cargo:rustc-link-lib=dylib=rmw");
+    println!("cargo:rustc-link-lib=dylib=rmw_implementation");
     println!("cargo:rustc-link-lib=dylib=rcutils");
 
     // Tell cargo to invalidate the built crate whenever the wrapper changes
     println!("cargo:rerun-if-changed=wrapper.h");
 
+    emit_rmw_implementation();
+
     let bindings = builder
         // The input header we would like to generate
         // bindings for.
@@ -53,3 +58,29 @@ fn main() {
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
 }
+
+/// Makes the rmw implementation selected with `RMW_IMPLEMENTATION` available to `#[cfg]`
+///
+/// Sets `rmw_implementation = "<name>"` and one of `rmw_fastrtps`, `rmw_cyclonedds` or
+/// `rmw_connext`, and passes the name on to dependents as `DEP_RCL_RMW_IMPLEMENTATION`.
+/// Nothing is set when the variable is unset, in which case the distro's default is used.
+/// `rmw_implementation` picks the middleware when the program starts, so this can differ from
+/// the one in use; `rmw_get_implementation_identifier` tells which one that is.
+fn emit_rmw_implementation() {
+    println!("cargo:rerun-if-env-changed=RMW_IMPLEMENTATION");
+    let rmw_implementation = match env::var("RMW_IMPLEMENTATION") {
+        Ok(rmw_implementation) if !rmw_implementation.is_empty() => rmw_implementation,
+        _ => return,
+    };
+
+    println!(
+        "cargo:rustc-cfg=rmw_implementation=\"{}\"",
+        rmw_implementation
+    );
+    for family in &["rmw_fastrtps", "rmw_cyclonedds", "rmw_connext"] {
+        if rmw_implementation.starts_with(family) {
+            println!("cargo:rustc-cfg={}", family);
+        }
+    }
+    println!("cargo:rmw_implementation={}", rmw_implementation);
+}
@@ -4,10 +4,12 @@
 #include <rcl/client.h>
 #include <rcl/service.h>
 #include <rcl/timer.h>
+#include <rcl/validate_topic_name.h>
 #include <rcl/init.h>
 #include <rcl/wait.h>
 #include <rcl/guard_condition.h>
 #include <rcl/graph.h>
+#include <rcl/remap.h>
 #include <rcl/allocator.h>
 #include <rcl/time.h>
 #include <rcl/error_handling.h>
@@ -15,5 +17,14 @@
 #include <rcl/types.h>
 #include <rcl/visibility_control.h>
 
+#include <rcl_lifecycle/rcl_lifecycle.h>
+
+#include <rcl_yaml_param_parser/parser.h>
+
+#include <rmw/rmw.h>
 #include <rmw/validate_node_name.h>
 #include <rmw/validate_namespace.h>
+#include <rmw/validate_full_topic_name.h>
+
+#include <rcutils/logging.h>
+#include <rcutils/shared_library.h>
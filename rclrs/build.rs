@@ -0,0 +1,19 @@
+use std::env;
+
+/// Sets the same rmw cfg flags as `rcl_sys`, see its build script
+fn main() {
+    let rmw_implementation = match env::var("DEP_RCL_RMW_IMPLEMENTATION") {
+        Ok(rmw_implementation) => rmw_implementation,
+        Err(_) => return,
+    };
+
+    println!(
+        "cargo:rustc-cfg=rmw_implementation=\"{}\"",
+        rmw_implementation
+    );
+    for family in &["rmw_fastrtps", "rmw_cyclonedds", "rmw_connext"] {
+        if rmw_implementation.starts_with(family) {
+            println!("cargo:rustc-cfg={}", family);
+        }
+    }
+}
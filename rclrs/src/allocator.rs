@@ -0,0 +1,19 @@
+use rcl_sys::*;
+
+/// Source of the `rcl_allocator_t` that rcl uses for its internal allocations
+///
+/// The returned allocator may point to state owned by the implementor, so entities keep the
+/// allocator they were created with alive through an `Rc` for as long as they exist.
+pub trait Allocator {
+    fn rcl_allocator(&self) -> rcl_allocator_t;
+}
+
+/// The default rcutils allocator, backed by the system `malloc`/`free`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultAllocator;
+
+impl Allocator for DefaultAllocator {
+    fn rcl_allocator(&self) -> rcl_allocator_t {
+        unsafe { rcutils_get_default_allocator() }
+    }
+}
@@ -0,0 +1,184 @@
+use crate::error::{RclError, RclResult, ToRclResult};
+use crate::node::parameter::{command_line_overrides, param_files};
+use crate::{Allocator, DefaultAllocator, ParameterValue};
+use rcl_sys::*;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::rc::Rc;
+
+/// ROS arguments parsed without initializing a context, e.g. to inspect them in a launcher
+///
+/// ```ignore
+/// let arguments = parse_arguments(&std::env::args().collect::<Vec<_>>())?;
+/// let namespace = arguments.remapped_node_namespace("my_node")?;
+/// ```
+pub struct ParsedArguments {
+    handle: rcl_arguments_t,
+    arguments: Vec<String>,
+    allocator: Rc<dyn Allocator>,
+}
+
+/// Parses `arguments` like [`crate::Context::new`] does, the first one being the program name
+///
+/// Only the arguments within a `--ros-args` scope are taken as ROS arguments.
+pub fn parse_arguments(arguments: &[String]) -> RclResult<ParsedArguments> {
+    let c_arguments = arguments
+        .iter()
+        .map(|argument| CString::new(argument.as_str()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| RclError::InvalidArgument)?;
+    let argv: Vec<*const c_char> = c_arguments.iter().map(|arg| arg.as_ptr()).collect();
+    let allocator: Rc<dyn Allocator> = Rc::new(DefaultAllocator);
+
+    let mut handle = unsafe { rcl_get_zero_initialized_arguments() };
+    unsafe {
+        rcl_parse_arguments(
+            argv.len() as c_int,
+            argv.as_ptr(),
+            allocator.rcl_allocator(),
+            &mut handle as *mut _,
+        )
+        .ok()?;
+    }
+
+    Ok(ParsedArguments {
+        handle,
+        arguments: arguments.to_vec(),
+        allocator,
+    })
+}
+
+impl ParsedArguments {
+    /// Returns the arguments outside of any `--ros-args` scope, starting with the program name
+    pub fn non_ros_arguments(&self) -> RclResult<Vec<String>> {
+        let count = unsafe { rcl_arguments_get_count_unparsed(&self.handle as *const _) };
+        let mut indices: *mut c_int = std::ptr::null_mut();
+        unsafe {
+            rcl_arguments_get_unparsed(
+                &self.handle as *const _,
+                self.allocator.rcl_allocator(),
+                &mut indices as *mut _,
+            )
+            .ok()?;
+        }
+        Ok(self.take_arguments(indices, count))
+    }
+
+    /// Returns the arguments within a `--ros-args` scope that rcl didn't recognize
+    pub fn unknown_ros_arguments(&self) -> RclResult<Vec<String>> {
+        let count = unsafe { rcl_arguments_get_count_unparsed_ros(&self.handle as *const _) };
+        let mut indices: *mut c_int = std::ptr::null_mut();
+        unsafe {
+            rcl_arguments_get_unparsed_ros(
+                &self.handle as *const _,
+                self.allocator.rcl_allocator(),
+                &mut indices as *mut _,
+            )
+            .ok()?;
+        }
+        Ok(self.take_arguments(indices, count))
+    }
+
+    /// Returns the paths of the `--params-file` arguments
+    pub fn param_files(&self) -> RclResult<Vec<String>> {
+        Ok(param_files(&self.handle, self.allocator.as_ref())?
+            .iter()
+            .map(|path| {
+                CStr::from_bytes_with_nul(path)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect())
+    }
+
+    /// Returns the values of the `-p name:=value` arguments for a node, e.g. `"/ns/node"`
+    pub fn parameter_overrides(
+        &self,
+        node_fully_qualified_name: &str,
+    ) -> RclResult<HashMap<String, ParameterValue>> {
+        let mut overrides = HashMap::new();
+        command_line_overrides(&self.handle, node_fully_qualified_name, &mut overrides)?;
+        Ok(overrides)
+    }
+
+    /// Returns the name a node called `node_name` is remapped to by `-r __node:=...`, if any
+    pub fn remapped_node_name(&self, node_name: &str) -> RclResult<Option<String>> {
+        let node_name = CString::new(node_name).map_err(|_| RclError::NodeInvalidName)?;
+        let mut output: *mut c_char = std::ptr::null_mut();
+        unsafe {
+            rcl_remap_node_name(
+                &self.handle as *const _,
+                std::ptr::null(),
+                node_name.as_ptr(),
+                self.allocator.rcl_allocator(),
+                &mut output as *mut _,
+            )
+            .ok()?;
+        }
+        Ok(self.take_string(output))
+    }
+
+    /// Returns the namespace a node called `node_name` is moved to by `-r __ns:=...`, if any
+    pub fn remapped_node_namespace(&self, node_name: &str) -> RclResult<Option<String>> {
+        let node_name = CString::new(node_name).map_err(|_| RclError::NodeInvalidName)?;
+        let mut output: *mut c_char = std::ptr::null_mut();
+        unsafe {
+            rcl_remap_node_namespace(
+                &self.handle as *const _,
+                std::ptr::null(),
+                node_name.as_ptr(),
+                self.allocator.rcl_allocator(),
+                &mut output as *mut _,
+            )
+            .ok()?;
+        }
+        Ok(self.take_string(output))
+    }
+
+    pub fn as_raw(&self) -> *const rcl_arguments_t {
+        &self.handle as *const _
+    }
+
+    /// Looks up the arguments at the indices rcl allocated, and frees them
+    fn take_arguments(&self, indices: *mut c_int, count: c_int) -> Vec<String> {
+        // Nothing is allocated when there are no such arguments
+        if indices.is_null() {
+            return vec![];
+        }
+        let arguments = unsafe { std::slice::from_raw_parts(indices, count.max(0) as usize) }
+            .iter()
+            .filter_map(|&index| self.arguments.get(index as usize).cloned())
+            .collect();
+        self.deallocate(indices as *mut c_void);
+        arguments
+    }
+
+    /// Copies a string rcl allocated, if any, and frees it
+    fn take_string(&self, string: *mut c_char) -> Option<String> {
+        if string.is_null() {
+            return None;
+        }
+        let owned = unsafe { CStr::from_ptr(string) }
+            .to_string_lossy()
+            .into_owned();
+        self.deallocate(string as *mut c_void);
+        Some(owned)
+    }
+
+    fn deallocate(&self, pointer: *mut c_void) {
+        let rcl_allocator = self.allocator.rcl_allocator();
+        unsafe {
+            rcl_allocator.deallocate.unwrap()(pointer, rcl_allocator.state);
+        }
+    }
+}
+
+impl Drop for ParsedArguments {
+    fn drop(&mut self) {
+        unsafe {
+            rcl_arguments_fini(&mut self.handle as *mut _).unwrap_in_drop("rcl_arguments_fini");
+        }
+    }
+}
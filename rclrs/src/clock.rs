@@ -0,0 +1,122 @@
+use crate::error::{RclResult, ToRclResult};
+use crate::{Allocator, Handle};
+use rcl_sys::*;
+use std::cell::{Ref, RefCell, RefMut};
+use std::rc::Rc;
+
+/// Time source of a [`Clock`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockType {
+    /// Follows simulated time when the override is enabled, system time otherwise
+    RosTime,
+    /// Wall time, which may jump
+    SystemTime,
+    /// Monotonic time, which never jumps
+    SteadyTime,
+}
+
+impl From<ClockType> for rcl_clock_type_t {
+    fn from(clock_type: ClockType) -> Self {
+        match clock_type {
+            ClockType::RosTime => rcl_clock_type_t::RCL_ROS_TIME,
+            ClockType::SystemTime => rcl_clock_type_t::RCL_SYSTEM_TIME,
+            ClockType::SteadyTime => rcl_clock_type_t::RCL_STEADY_TIME,
+        }
+    }
+}
+
+/// A point in time, as read from a [`Clock`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Time {
+    /// Nanoseconds since the epoch of the clock
+    pub nanoseconds: i64,
+    pub clock_type: ClockType,
+}
+
+pub struct Clock {
+    handle: RefCell<rcl_clock_t>,
+    clock_type: ClockType,
+    // The rcl clock keeps a copy of the allocator, so its state has to outlive the clock
+    _allocator: Rc<dyn Allocator>,
+}
+
+impl Clock {
+    pub fn new(clock_type: ClockType, allocator: Rc<dyn Allocator>) -> RclResult<Self> {
+        let mut clock_handle = unsafe { std::mem::zeroed::<rcl_clock_t>() };
+        let mut rcl_allocator = allocator.rcl_allocator();
+
+        unsafe {
+            rcl_clock_init(
+                clock_type.into(),
+                &mut clock_handle as *mut _,
+                &mut rcl_allocator as *mut _,
+            )
+            .ok()?;
+        }
+
+        Ok(Self {
+            handle: RefCell::new(clock_handle),
+            clock_type,
+            _allocator: allocator,
+        })
+    }
+
+    pub fn clock_type(&self) -> ClockType {
+        self.clock_type
+    }
+
+    /// Returns the current time of this clock
+    pub fn now(&self) -> RclResult<Time> {
+        let handle = &mut *self.get_mut();
+        let mut nanoseconds = 0;
+
+        unsafe {
+            rcl_clock_get_now(handle as *mut _, &mut nanoseconds as *mut _).ok()?;
+        }
+
+        Ok(Time {
+            nanoseconds,
+            clock_type: self.clock_type,
+        })
+    }
+
+    /// Makes a [`ClockType::RosTime`] clock report the time set through
+    /// [`Clock::set_ros_time_override`] instead of system time
+    pub fn enable_ros_time_override(&self) -> RclResult {
+        let handle = &mut *self.get_mut();
+        unsafe { rcl_enable_ros_time_override(handle as *mut _).ok() }
+    }
+
+    pub fn disable_ros_time_override(&self) -> RclResult {
+        let handle = &mut *self.get_mut();
+        unsafe { rcl_disable_ros_time_override(handle as *mut _).ok() }
+    }
+
+    /// Sets the time reported while the ROS time override is enabled, in nanoseconds
+    pub fn set_ros_time_override(&self, nanoseconds: i64) -> RclResult {
+        let handle = &mut *self.get_mut();
+        unsafe { rcl_set_ros_time_override(handle as *mut _, nanoseconds).ok() }
+    }
+}
+
+impl<'a> Handle<rcl_clock_t> for &'a Clock {
+    type DerefT = Ref<'a, rcl_clock_t>;
+    type DerefMutT = RefMut<'a, rcl_clock_t>;
+
+    fn get(self) -> Self::DerefT {
+        self.handle.borrow()
+    }
+
+    fn get_mut(self) -> Self::DerefMutT {
+        self.handle.borrow_mut()
+    }
+}
+
+impl Drop for Clock {
+    fn drop(&mut self) {
+        let handle = &mut *self.get_mut();
+        unsafe {
+            rcl_clock_fini(handle as *mut _).unwrap_in_drop("rcl_clock_fini");
+        }
+    }
+}
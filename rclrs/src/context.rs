@@ -1,84 +1,455 @@
-use crate::error::{RclResult, ToRclResult};
-use crate::{Handle, Node};
+use crate::error::{unwrap_in_drop, RclError, RclResult, ToRclResult};
+use crate::{signal, Allocator, Clock, ClockType, DefaultAllocator, Handle, Node};
 use rcl_sys::*;
-use std::cell::{Ref, RefCell, RefMut};
+use std::cell::{Cell, Ref, RefCell, RefMut};
 use std::env;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
 
-pub struct ContextHandle(RefCell<rcl_context_t>);
+/// Owner of the `rcl_context_t`
+///
+/// A context is only ever used from the thread that created it, so a `RefCell` is enough here:
+/// a lock would add overhead without protecting anything. Most operations only need to read
+/// the context, so prefer [`Handle::get`] over [`Handle::get_mut`] to avoid borrow conflicts,
+/// e.g. when a callback checks [`Context::ok`] while the context is in use by [`crate::spin`].
+pub struct ContextHandle {
+    handle: RefCell<rcl_context_t>,
+    shutdown_callbacks: RefCell<Vec<Box<dyn FnOnce()>>>,
+    shutdown_on_signal: Cell<bool>,
+    /// The signals received before the context was initialized, which it doesn't act on
+    ignored_signals: Cell<usize>,
+    // Shared by the steady timers of all nodes, created along with the first one
+    steady_clock: RefCell<Option<Rc<Clock>>>,
+}
+
+impl ContextHandle {
+    /// Runs the shutdown callbacks, newest first, and shuts the context down
+    ///
+    /// The callbacks are drained while they run, so each of them runs exactly once no matter
+    /// how often this is called.
+    fn shutdown(&self) -> RclResult {
+        loop {
+            // Don't hold the borrow while the callback runs, it may register another one
+            let callback = self.shutdown_callbacks.borrow_mut().pop();
+            match callback {
+                Some(callback) => callback(),
+                None => break,
+            }
+        }
+
+        let handle = &mut *self.get_mut();
+        // It is only valid if it was initialized and not shut down yet
+        unsafe {
+            if rcl_context_is_valid(handle as *mut _) {
+                rcl_shutdown(handle as *mut _).ok()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the steady clock the context's timers share, creating it on first use
+    ///
+    /// Timers keep the clock alive, so it outlives every timer created on it.
+    pub(crate) fn steady_clock(&self, allocator: Rc<dyn Allocator>) -> RclResult<Rc<Clock>> {
+        let mut steady_clock = self.steady_clock.borrow_mut();
+        if let Some(clock) = &*steady_clock {
+            return Ok(clock.clone());
+        }
+        let clock = Rc::new(Clock::new(ClockType::SteadyTime, allocator)?);
+        *steady_clock = Some(clock.clone());
+        Ok(clock)
+    }
+
+    /// Whether SIGINT and SIGTERM shut this context down, see [`InitOptions::shutdown_on_signal`]
+    pub(crate) fn shuts_down_on_signal(&self) -> bool {
+        self.shutdown_on_signal.get()
+    }
+
+    /// Whether the context handles termination signals and one was received since it was
+    /// initialized
+    pub(crate) fn termination_requested(&self) -> bool {
+        self.shutdown_on_signal.get()
+            && signal::termination_signal_count() > self.ignored_signals.get()
+    }
+
+    /// Shuts the context down if it handles termination signals and one was received
+    pub(crate) fn shutdown_if_signaled(&self) -> RclResult {
+        if self.termination_requested() {
+            self.shutdown()?;
+        }
+        Ok(())
+    }
+}
 
 impl<'a> Handle<rcl_context_t> for &'a ContextHandle {
     type DerefT = Ref<'a, rcl_context_t>;
     type DerefMutT = RefMut<'a, rcl_context_t>;
 
     fn get(self) -> Self::DerefT {
-        self.0.borrow()
+        self.handle.borrow()
     }
 
     fn get_mut(self) -> Self::DerefMutT {
-        self.0.borrow_mut()
+        self.handle.borrow_mut()
     }
 }
 
 impl Drop for ContextHandle {
     fn drop(&mut self) {
+        // The context has to be shut down before it can be finalized
+        unwrap_in_drop(self.shutdown(), "rcl_shutdown");
         let handle = &mut *self.get_mut();
         unsafe {
-            rcl_shutdown(handle as *mut _);
+            rcl_context_fini(handle as *mut _).unwrap_in_drop("rcl_context_fini");
         }
     }
 }
 
+/// Settings for [`Context::new_with_options`] that apply to all nodes of the context
+#[derive(Clone, Debug, Default)]
+pub struct InitOptions {
+    /// Security enclave the nodes are part of, whose SROS2 artifacts are loaded from the
+    /// `ROS_SECURITY_KEYSTORE`, e.g. `"/my/enclave"`
+    ///
+    /// Takes precedence over an `--enclave` command line argument.
+    pub enclave: Option<String>,
+    /// Whether the middleware only communicates with other processes on this host
+    ///
+    /// Without a value it follows the `ROS_LOCALHOST_ONLY` environment variable. This and the
+    /// enclave are the only middleware settings rcl takes from the init options; security
+    /// settings always come from the `ROS_SECURITY_*` environment variables.
+    pub localhost_only: Option<bool>,
+    /// Middleware to load, e.g. `"rmw_cyclonedds_cpp"`, instead of the one selected by the
+    /// `RMW_IMPLEMENTATION` environment variable
+    ///
+    /// The middleware is loaded once per process, by whichever call into it comes first, so
+    /// this sets that variable for the whole process. Initializing fails with
+    /// [`RclError::MismatchedRmwId`] if a different middleware was already loaded.
+    pub rmw_implementation: Option<String>,
+    /// Whether SIGINT and SIGTERM shut the context down, so [`Context::ok`] returns `false` and
+    /// [`crate::spin`] returns, e.g. when a container is stopped
+    ///
+    /// rcl installs no signal handlers, so without this both signals terminate the process right
+    /// away. It is off by default, for applications that handle the signals themselves: the
+    /// handlers are process-wide and replace any installed before. The shutdown callbacks run
+    /// as with [`Context::shutdown`], from the spin or [`Context::ok`] call that notices it.
+    /// Only signals received after the context was initialized count, and initializing fails
+    /// with [`RclError::Error`] if the handlers can't be installed.
+    pub shutdown_on_signal: bool,
+}
+
 pub struct Context {
     pub handle: Rc<ContextHandle>,
+    pub(crate) allocator: Rc<dyn Allocator>,
 }
 
 impl Context {
-    fn init(&mut self) -> RclResult {
-        let args: Vec<CString> = env::args()
+    fn init(&mut self, options: &InitOptions) -> RclResult {
+        if let Some(rmw_implementation) = &options.rmw_implementation {
+            env::set_var("RMW_IMPLEMENTATION", rmw_implementation);
+        }
+        if options.shutdown_on_signal {
+            signal::install_termination_handlers()?;
+            self.handle
+                .ignored_signals
+                .set(signal::termination_signal_count());
+            self.handle.shutdown_on_signal.set(true);
+        }
+
+        let mut args: Vec<CString> = env::args()
             .filter_map(|arg| CString::new(arg).ok())
             .collect();
+        // rcl only takes the enclave from the arguments, later arguments win
+        if let Some(enclave) = &options.enclave {
+            args.push(CString::new("--ros-args").unwrap());
+            args.push(CString::new("--enclave").unwrap());
+            args.push(CString::new(enclave.as_str()).map_err(|_| RclError::InvalidArgument)?);
+        }
 
         let c_args: Vec<*const c_char> = args.iter().map(|arg| arg.as_ptr()).collect();
         let handle = &mut *self.handle.get_mut();
 
         unsafe {
-            let allocator = rcutils_get_default_allocator();
+            let allocator = self.allocator.rcl_allocator();
             let mut init_options = rcl_get_zero_initialized_init_options();
-            rcl_init_options_init(&mut init_options as *mut _, allocator);
-            rcl_init(
+            rcl_init_options_init(&mut init_options as *mut _, allocator).ok()?;
+            if let Some(localhost_only) = options.localhost_only {
+                let rmw_init_options =
+                    &mut *rcl_init_options_get_rmw_init_options(&mut init_options as *mut _);
+                rmw_init_options.localhost_only = if localhost_only {
+                    rmw_localhost_only_t::RMW_LOCALHOST_ONLY_ENABLED
+                } else {
+                    rmw_localhost_only_t::RMW_LOCALHOST_ONLY_DISABLED
+                };
+            }
+            let result = rcl_init(
                 c_args.len() as i32,
                 c_args.as_ptr(),
                 &init_options as *const _,
                 handle as *mut _,
             )
-            .ok()?;
+            .ok();
+            // On failure rcl resets the context, so it can be initialized again
             rcl_init_options_fini(&mut init_options as *mut _).ok()?;
+            result?;
         }
 
-        Ok(())
+        match &options.rmw_implementation {
+            Some(rmw_implementation) if *rmw_implementation != self.rmw_implementation() => {
+                Err(RclError::MismatchedRmwId)
+            }
+            _ => Ok(()),
+        }
     }
 
     pub fn ok(&self) -> bool {
-        let handle = &mut *self.handle.get_mut();
-        unsafe { rcl_context_is_valid(handle as *mut _) }
+        // A failed shutdown leaves the context valid, which is then what this reports
+        let _ = self.handle.shutdown_if_signaled();
+        let handle = &*self.handle.get();
+        // rcl only reads the context here, its signature just isn't const-correct yet
+        unsafe { rcl_context_is_valid(handle as *const _ as *mut _) }
+    }
+
+    /// Registers `callback` to run when the context shuts down
+    ///
+    /// Shutdown happens on [`Context::shutdown`] or, at the latest, when the last user of the
+    /// context is dropped. Callbacks run in reverse order of registration, before rcl is shut
+    /// down, so they can still use ROS.
+    pub fn on_shutdown<F>(&self, callback: F)
+    where
+        F: FnOnce() + 'static,
+    {
+        self.handle
+            .shutdown_callbacks
+            .borrow_mut()
+            .push(Box::new(callback));
+    }
+
+    /// Runs the shutdown callbacks and shuts the context down, after which [`Context::ok`]
+    /// returns `false`
+    pub fn shutdown(&self) -> RclResult {
+        self.handle.shutdown()
+    }
+
+    /// Returns the identifier of the rmw implementation in use, e.g. `"rmw_fastrtps_cpp"`
+    pub fn rmw_implementation(&self) -> String {
+        let identifier = unsafe { rmw_get_implementation_identifier() };
+        if identifier.is_null() {
+            return String::new();
+        }
+        unsafe { CStr::from_ptr(identifier) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Returns the ROS domain ID the context's nodes join
+    ///
+    /// In this distro rcl has no domain ID per context: every node reads `ROS_DOMAIN_ID` when it
+    /// is created, which is what this does as well. [`crate::Node::options`] reports the domain
+    /// ID of a specific node.
+    pub fn domain_id(&self) -> usize {
+        // Like rcl, which parses the variable with strtoul, anything but a number is domain 0
+        std::env::var("ROS_DOMAIN_ID")
+            .ok()
+            .and_then(|domain_id| domain_id.trim().parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Returns the security enclave the context's nodes are part of, `"/"` unless configured
+    pub fn enclave(&self) -> Option<String> {
+        let handle = &*self.handle.get();
+        unsafe {
+            let init_options = rcl_context_get_init_options(handle as *const _);
+            if init_options.is_null() {
+                return None;
+            }
+            // Only read, the context owns the options
+            let rmw_init_options = rcl_init_options_get_rmw_init_options(init_options as *mut _);
+            match rmw_init_options.as_ref() {
+                Some(rmw_init_options) if !rmw_init_options.enclave.is_null() => Some(
+                    CStr::from_ptr(rmw_init_options.enclave)
+                        .to_string_lossy()
+                        .into_owned(),
+                ),
+                _ => None,
+            }
+        }
+    }
+
+    /// Returns the underlying rcl context, for passing it to C code
+    ///
+    /// The context stays owned by this context and is only valid for as long as it exists, so
+    /// it must not be shut down or finalized through the pointer.
+    pub fn as_raw(&self) -> *const rcl_context_t {
+        self.handle.handle.as_ptr()
+    }
+
+    /// Like [`Context::as_raw`], for rcl functions that take a mutable context
+    pub fn as_raw_mut(&self) -> *mut rcl_context_t {
+        self.handle.handle.as_ptr()
     }
 
     pub fn create_node(&self, node_name: &str) -> RclResult<Node> {
         Node::new(node_name, self)
     }
+
+    /// Creates a context whose rcl allocations, and those of its nodes, use `allocator`
+    pub fn new_with_allocator(allocator: Rc<dyn Allocator>) -> RclResult<Self> {
+        Self::new_with_options(InitOptions::default(), allocator)
+    }
+
+    pub fn new_with_options(options: InitOptions, allocator: Rc<dyn Allocator>) -> RclResult<Self> {
+        let mut context = Self::uninitialized(allocator);
+        context.init(&options)?;
+        Ok(context)
+    }
+
+    /// Creates a context, retrying up to `attempts` times while initialization fails transiently
+    ///
+    /// The delay before each retry starts at `backoff` and doubles after every attempt. Errors
+    /// that retrying cannot fix, like invalid arguments, are returned right away.
+    pub fn new_with_retry(attempts: u32, backoff: Duration) -> RclResult<Self> {
+        let mut context = Self::uninitialized(Rc::new(DefaultAllocator));
+        retry_init(
+            attempts,
+            backoff,
+            || context.init(&InitOptions::default()),
+            thread::sleep,
+        )?;
+        Ok(context)
+    }
+
+    fn uninitialized(allocator: Rc<dyn Allocator>) -> Self {
+        Self {
+            handle: Rc::new(ContextHandle {
+                handle: RefCell::new(unsafe { rcl_get_zero_initialized_context() }),
+                shutdown_callbacks: RefCell::new(vec![]),
+                shutdown_on_signal: Cell::new(false),
+                ignored_signals: Cell::new(0),
+                steady_clock: RefCell::new(None),
+            }),
+            allocator,
+        }
+    }
+}
+
+/// Whether an initialization error may go away by itself, e.g. while the network comes up
+fn is_transient(error: &RclError) -> bool {
+    matches!(error, RclError::Error | RclError::Timeout)
+}
+
+/// Calls `init` until it succeeds, sleeping with a doubling delay in between, see
+/// [`Context::new_with_retry`]
+///
+/// The delay stops growing once doubling it would overflow.
+fn retry_init<I, S>(attempts: u32, backoff: Duration, mut init: I, mut sleep: S) -> RclResult
+where
+    I: FnMut() -> RclResult,
+    S: FnMut(Duration),
+{
+    let mut delay = backoff;
+    for attempt in 1..=attempts.max(1) {
+        match init() {
+            Ok(()) => return Ok(()),
+            Err(error) if attempt < attempts && is_transient(&error) => {
+                sleep(delay);
+                delay = delay.checked_mul(2).unwrap_or(delay);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+    unreachable!()
 }
 
 impl Default for Context {
     fn default() -> Self {
-        let mut context = Self {
-            handle: Rc::new(ContextHandle(RefCell::new(unsafe {
-                rcl_get_zero_initialized_context()
-            }))),
-        };
-        context.init().unwrap();
-        context
+        Self::new_with_allocator(Rc::new(DefaultAllocator)).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `retry_init` on the given results, returning its result and the delays slept
+    fn retry(
+        attempts: u32,
+        backoff: Duration,
+        results: Vec<RclResult>,
+    ) -> (RclResult, Vec<Duration>, usize) {
+        let mut results = results.into_iter();
+        let mut calls = 0;
+        let mut delays = vec![];
+        let result = retry_init(
+            attempts,
+            backoff,
+            || {
+                calls += 1;
+                results.next().unwrap()
+            },
+            |delay| delays.push(delay),
+        );
+        (result, delays, calls)
+    }
+
+    #[test]
+    fn transient_errors_are_retried_with_a_doubling_delay() {
+        let (result, delays, calls) = retry(
+            4,
+            Duration::from_millis(10),
+            vec![Err(RclError::Error), Err(RclError::Timeout), Ok(())],
+        );
+        assert!(result.is_ok());
+        assert_eq!(calls, 3);
+        assert_eq!(
+            delays,
+            vec![Duration::from_millis(10), Duration::from_millis(20)]
+        );
+    }
+
+    #[test]
+    fn the_last_error_is_returned_when_the_attempts_run_out() {
+        let (result, delays, calls) = retry(
+            2,
+            Duration::from_millis(10),
+            vec![Err(RclError::Error), Err(RclError::Timeout)],
+        );
+        assert!(matches!(result, Err(RclError::Timeout)));
+        assert_eq!(calls, 2);
+        assert_eq!(delays.len(), 1);
+    }
+
+    #[test]
+    fn other_errors_are_returned_right_away() {
+        let (result, delays, calls) = retry(
+            5,
+            Duration::from_millis(10),
+            vec![Err(RclError::InvalidArgument)],
+        );
+        assert!(matches!(result, Err(RclError::InvalidArgument)));
+        assert_eq!(calls, 1);
+        assert!(delays.is_empty());
+    }
+
+    #[test]
+    fn zero_attempts_still_try_once() {
+        let (result, _, calls) = retry(0, Duration::from_millis(10), vec![Ok(())]);
+        assert!(result.is_ok());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn the_delay_stops_growing_instead_of_overflowing() {
+        let backoff = Duration::new(u64::MAX / 2 + 1, 0);
+        let (result, delays, _) = retry(
+            3,
+            backoff,
+            vec![Err(RclError::Error), Err(RclError::Error), Ok(())],
+        );
+        assert!(result.is_ok());
+        assert_eq!(delays, vec![backoff, backoff]);
     }
 }
@@ -1,14 +1,77 @@
+use failure::Fail;
 use rcl_sys::*;
+pub use rclrs_common::error::MessageValidationError;
 pub use rclrs_common::error::RCLStatusCode as RclError;
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::os::raw::c_char;
 
 /// Error code that ROS nodes written in Rust should be returning from `main`
 pub type RclResult<T = ()> = Result<T, RclError>;
 
+/// Error returned when publishing a message fails
+#[derive(Debug, Fail)]
+pub enum PublishError {
+    #[fail(display = "message is invalid: {}", _0)]
+    Validation(#[cause] MessageValidationError),
+    /// The publisher can't be used anymore, e.g. because its context was shut down
+    #[fail(display = "invalid publisher: {}", message)]
+    PublisherInvalid { message: String },
+    #[fail(display = "failed to allocate memory: {}", message)]
+    BadAlloc { message: String },
+    #[fail(display = "invalid argument: {}", message)]
+    InvalidArgument { message: String },
+    /// Any other failure, e.g. in the middleware
+    #[fail(display = "{}: {}", error, message)]
+    Rcl {
+        #[cause]
+        error: RclError,
+        message: String,
+    },
+}
+
+impl PublishError {
+    /// Converts a failed `rcl_ret_t`, with the error message rcl set along with it
+    ///
+    /// Has to be called right after the failing call, instead of [`ToRclResult::ok`], which
+    /// would take the error state first.
+    pub(crate) fn from_rcl_ret(ret: rcl_ret_t) -> Self {
+        let message = record_error_state()
+            .map(|state| state.message)
+            .unwrap_or_default();
+        match RclError::from(ret) {
+            RclError::PublisherInvalid => PublishError::PublisherInvalid { message },
+            RclError::BadAlloc => PublishError::BadAlloc { message },
+            RclError::InvalidArgument => PublishError::InvalidArgument { message },
+            error => PublishError::Rcl { error, message },
+        }
+    }
+}
+
+impl From<MessageValidationError> for PublishError {
+    fn from(error: MessageValidationError) -> Self {
+        PublishError::Validation(error)
+    }
+}
+
+impl From<RclError> for PublishError {
+    fn from(error: RclError) -> Self {
+        PublishError::Rcl {
+            error,
+            message: String::new(),
+        }
+    }
+}
+
 pub(crate) trait ToRclResult {
     fn ok(&self) -> RclResult<()>;
 
-    fn unwrap(&self) {
-        self.ok().unwrap();
+    /// Panics on error, for use in `Drop` impls
+    ///
+    /// While the thread is already unwinding, a second panic would abort the process,
+    /// so the error is only logged then.
+    fn unwrap_in_drop(&self, operation: &str) {
+        unwrap_in_drop(self.ok(), operation);
     }
 }
 
@@ -17,7 +80,77 @@ impl ToRclResult for rcl_ret_t {
         if *self as u32 == RCL_RET_OK {
             Ok(())
         } else {
+            record_error_state();
             Err(RclError::from(*self))
         }
     }
 }
+
+/// See [`ToRclResult::unwrap_in_drop`]
+pub(crate) fn unwrap_in_drop(result: RclResult, operation: &str) {
+    if let Err(error) = result {
+        if std::thread::panicking() {
+            eprintln!("{} failed during unwinding: {}", operation, error);
+        } else {
+            panic!("{} failed: {}", operation, error);
+        }
+    }
+}
+
+/// Why and where the last failing rcl call on this thread failed, as reported by rcl
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorState {
+    pub message: String,
+    /// Source file in rcl, or one of the libraries below it, that set the error
+    pub file: String,
+    pub line: u64,
+}
+
+thread_local! {
+    static LAST_ERROR_STATE: RefCell<Option<ErrorState>> = const { RefCell::new(None) };
+}
+
+/// Returns the error state of the last rcl call on this thread that returned an error
+///
+/// `RclError` only carries the return code, this has the details to go with it.
+pub fn last_error_state() -> Option<ErrorState> {
+    LAST_ERROR_STATE.with(|state| state.borrow().clone())
+}
+
+/// Moves the error state out of rcutils, which has to be reset before the next error is set
+///
+/// Called right after a call failed, and returns the error state that call set. A failing call
+/// that didn't set one clears the recorded state, so it isn't mistaken for an older one.
+pub(crate) fn record_error_state() -> Option<ErrorState> {
+    let error_state = unsafe {
+        if !rcutils_error_is_set() {
+            LAST_ERROR_STATE.with(|state| *state.borrow_mut() = None);
+            return None;
+        }
+        let error_state = &*rcutils_get_error_state();
+        let to_string = |chars: &[c_char]| {
+            CStr::from_ptr(chars.as_ptr())
+                .to_string_lossy()
+                .into_owned()
+        };
+        let error_state = ErrorState {
+            message: to_string(&error_state.message),
+            file: to_string(&error_state.file),
+            line: error_state.line_number,
+        };
+        rcutils_reset_error();
+        error_state
+    };
+    LAST_ERROR_STATE.with(|state| *state.borrow_mut() = Some(error_state.clone()));
+    Some(error_state)
+}
+
+/// rcutils return codes don't line up with rcl's, so they are not converted one-to-one
+pub(crate) fn rcutils_ok(ret: rcutils_ret_t) -> RclResult {
+    if ret as u32 == RCUTILS_RET_OK {
+        Ok(())
+    } else {
+        record_error_state();
+        Err(RclError::Error)
+    }
+}
@@ -1,15 +1,37 @@
+pub mod allocator;
+pub mod arguments;
+pub mod clock;
 pub mod context;
 pub mod error;
+pub mod logging;
 pub mod node;
+pub mod prelude;
 pub mod qos;
+pub mod rate;
+pub mod recorder;
+pub mod serialization;
+mod signal;
+pub mod validation;
 
+pub use self::allocator::*;
+pub use self::arguments::*;
+pub use self::clock::*;
 pub use self::context::*;
 pub use self::error::*;
+pub use self::logging::*;
 pub use self::node::*;
 pub use self::qos::*;
+pub use self::rate::*;
+pub use self::recorder::*;
+pub use self::serialization::*;
+pub use self::validation::*;
 
 use rcl_sys::*;
+use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::ops::{Deref, DerefMut};
+use std::rc::{Rc, Weak};
+use std::time::{Duration, Instant};
 
 pub trait Handle<T> {
     type DerefT: Deref<Target = T>;
@@ -21,8 +43,19 @@ pub trait Handle<T> {
 
 /// Wrapper around [`spin_once`]
 pub fn spin(node: &Node) -> RclResult {
-    while unsafe { rcl_context_is_valid(&mut *node.context.get_mut() as *mut _) } {
-        if let Some(error) = spin_once(node, 500).err() {
+    spin_nodes(&[node])
+}
+
+/// Like [`spin`], for several nodes of the same context, see [`spin_once_nodes`]
+///
+/// Returns right away if `nodes` is empty.
+pub fn spin_nodes(nodes: &[&Node]) -> RclResult {
+    let context = match nodes.first() {
+        Some(node) => node.context.clone(),
+        None => return Ok(()),
+    };
+    while unsafe { rcl_context_is_valid(&*context.get() as *const _ as *mut _) } {
+        if let Some(error) = spin_once_nodes(nodes, Some(Duration::from_nanos(500))).err() {
             match error {
                 RclError::Timeout => continue,
                 _ => return Err(error),
@@ -33,6 +66,54 @@ pub fn spin(node: &Node) -> RclResult {
     Ok(())
 }
 
+/// Runs [`spin_once`] `n` times, waiting at most `timeout` each time
+///
+/// A wait that times out still counts as one of the `n`, so this returns within `n * timeout`.
+/// Stops early once the context is shut down.
+pub fn spin_n(node: &Node, n: usize, timeout: Duration) -> RclResult {
+    for _ in 0..n {
+        if !unsafe { rcl_context_is_valid(&*node.context.get() as *const _ as *mut _) } {
+            break;
+        }
+        match spin_once(node, Some(timeout)) {
+            Ok(()) | Err(RclError::Timeout) => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(())
+}
+
+/// Spins the node until `predicate` returns true, e.g. once a callback has stored a result
+///
+/// `predicate` is checked before spinning and after every [`spin_once`], and the node keeps
+/// handling its other callbacks in between. Returns whether it became true before `timeout`
+/// elapsed, or before the context was shut down. Waits indefinitely if `timeout` is `None`.
+pub fn spin_until<P>(node: &Node, mut predicate: P, timeout: Option<Duration>) -> RclResult<bool>
+where
+    P: FnMut() -> bool,
+{
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    while !predicate() {
+        if !unsafe { rcl_context_is_valid(&*node.context.get() as *const _ as *mut _) } {
+            return Ok(false);
+        }
+        let remaining = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) => Some(remaining),
+                None => return Ok(false),
+            },
+            None => None,
+        };
+        match spin_once(node, remaining) {
+            Ok(()) | Err(RclError::Timeout) => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(true)
+}
+
 /// Main function for waiting.
 ///
 /// Following is a schematic representation of the interation of [`spin_once`] with ROS RCL FFI
@@ -69,69 +150,256 @@ pub fn spin(node: &Node) -> RclResult {
 ///         | rcl_wait_set_fini  |
 ///         +--------------------+
 ///
+/// Waits for at most `timeout`, or until there is work if it is `None`. A zero timeout only
+/// handles what is ready right now. Returns [`RclError::Timeout`] if nothing became ready.
 ///
-pub fn spin_once(node: &Node, timeout: i64) -> RclResult {
+/// The timeout is measured on a monotonic clock. If the wait wakes up without anything being
+/// ready, it waits again for whatever is left of the timeout; once something is ready,
+/// [`spin_once`] handles it and returns without waiting for the rest of the timeout.
+pub fn spin_once(node: &Node, timeout: Option<Duration>) -> RclResult {
+    spin_once_nodes(&[node], timeout)
+}
+
+/// Like [`spin_once`], for several nodes of the same context that share one wait set
+///
+/// The wait set is built from the nodes on every call, so nodes can be added to or removed
+/// from `nodes` between calls. One wake-up handles everything that is ready in any of them.
+/// Nodes of different contexts are refused with [`RclError::InvalidArgument`].
+pub fn spin_once_nodes(nodes: &[&Node], timeout: Option<Duration>) -> RclResult {
+    let first_node = match nodes.first() {
+        Some(node) => node,
+        None => return Err(RclError::WaitSetEmpty),
+    };
+    if nodes
+        .iter()
+        .any(|node| !Rc::ptr_eq(&node.context, &first_node.context))
+    {
+        return Err(RclError::InvalidArgument);
+    }
+
+    // Taken up front, so callbacks can create entities on the nodes while they are handled
+    let entities = WaitableEntities::new(nodes);
+
     // get an rcl_wait_set_t - All NULLs
     let mut wait_set_handle = unsafe { rcl_get_zero_initialized_wait_set() };
 
-    let number_of_subscriptions = node.subscriptions.len();
-    let number_of_guard_conditions = 0;
-    let number_of_timers = 0;
+    let number_of_subscriptions = entities.subscriptions.len();
+    let number_of_guard_conditions = entities.guard_conditions.len();
+    let number_of_timers = entities.timers.len();
     let number_of_clients = 0;
     let number_of_services = 0;
-    let number_of_events = 0;
+    let number_of_events = entities.events.len();
 
-    let context = &mut *node.context.get_mut();
+    {
+        let context = &mut *first_node.context.get_mut();
 
-    unsafe {
-        rcl_wait_set_init(
-            &mut wait_set_handle as *mut _,
-            number_of_subscriptions,
-            number_of_guard_conditions,
-            number_of_timers,
-            number_of_clients,
-            number_of_services,
-            number_of_events,
-            context,
-            rcutils_get_default_allocator(),
-        )
-        .ok()?;
+        unsafe {
+            rcl_wait_set_init(
+                &mut wait_set_handle as *mut _,
+                number_of_subscriptions,
+                number_of_guard_conditions,
+                number_of_timers,
+                number_of_clients,
+                number_of_services,
+                number_of_events,
+                context,
+                first_node.allocator.rcl_allocator(),
+            )
+            .ok()?;
+        }
     }
 
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let result = wait_and_handle(
+        &entities,
+        &mut wait_set_handle,
+        deadline,
+        &first_node.context,
+    );
+
+    // Also when waiting timed out, which is the common case when spinning
     unsafe {
-        rcl_wait_set_clear(&mut wait_set_handle as *mut _).ok()?;
-    }
-
-    for subscription in &node.subscriptions {
-        if let Some(subscription) = subscription.upgrade() {
-            let subscription_handle = &*subscription.handle().get();
-            unsafe {
-                rcl_wait_set_add_subscription(
-                    &mut wait_set_handle as *mut _,
-                    subscription_handle as *const _,
-                    std::ptr::null_mut(),
-                )
-                .ok()?;
-            }
+        rcl_wait_set_fini(&mut wait_set_handle as *mut _).ok()?;
+    }
+    first_node.context.shutdown_if_signaled()?;
+
+    result
+}
+
+/// The entities of some nodes that are still alive, in the order they are added to a wait set
+struct WaitableEntities {
+    subscriptions: Vec<Rc<dyn SubscriptionBase>>,
+    guard_conditions: Vec<Rc<GuardCondition>>,
+    timers: Vec<Rc<Timer>>,
+    events: Vec<Rc<QoSEvent>>,
+}
+
+impl WaitableEntities {
+    fn new(nodes: &[&Node]) -> Self {
+        /// Upgrades the entities that are still alive, and forgets the dropped ones
+        fn upgrade<T: ?Sized>(entities: &RefCell<Vec<Weak<T>>>) -> Vec<Rc<T>> {
+            let mut entities = entities.borrow_mut();
+            entities.retain(|entity| entity.strong_count() > 0);
+            entities.iter().filter_map(Weak::upgrade).collect()
+        }
+
+        WaitableEntities {
+            subscriptions: nodes
+                .iter()
+                .flat_map(|node| upgrade(&node.subscriptions))
+                .collect(),
+            guard_conditions: nodes
+                .iter()
+                .flat_map(|node| upgrade(&node.guard_conditions))
+                .collect(),
+            timers: nodes
+                .iter()
+                .flat_map(|node| upgrade(&node.timers))
+                .collect(),
+            events: nodes
+                .iter()
+                .flat_map(|node| upgrade(&node.events))
+                .collect(),
         }
     }
+}
 
-    unsafe {
-        rcl_wait(&mut wait_set_handle as *mut _, timeout).ok()?;
+fn wait_and_handle(
+    entities: &WaitableEntities,
+    wait_set_handle: &mut rcl_wait_set_t,
+    deadline: Option<Instant>,
+    context: &ContextHandle,
+) -> RclResult {
+    let poll_for_signals = context.shuts_down_on_signal();
+    loop {
+        // rcl_wait clears the entries that aren't ready, so everything is added again each time
+        add_to_wait_set(entities, wait_set_handle)?;
+
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        // A signal doesn't wake rcl_wait, so it is checked for in between shorter waits
+        let poll_period = match remaining {
+            Some(remaining) if remaining <= signal::SIGNAL_POLL_PERIOD => None,
+            _ if poll_for_signals => Some(signal::SIGNAL_POLL_PERIOD),
+            _ => None,
+        };
+        // rcl blocks forever on a negative timeout; timeouts too long for it are close enough
+        // to that
+        let timeout = match poll_period.or(remaining) {
+            Some(timeout) => i64::try_from(timeout.as_nanos()).unwrap_or(i64::MAX),
+            None => -1,
+        };
+        match unsafe { rcl_wait(wait_set_handle as *mut _, timeout) }.ok() {
+            Ok(()) => {}
+            Err(RclError::Timeout) if poll_period.is_some() => {}
+            Err(error) => return Err(error),
+        }
+
+        // The caller shuts the context down, nothing is handled anymore
+        if context.termination_requested() {
+            return Ok(());
+        }
+        if wait_set_has_ready_entries(wait_set_handle) {
+            break;
+        }
     }
 
-    for subscription in &node.subscriptions {
-        if let Some(subscription) = subscription.upgrade() {
-            let mut message = subscription.create_message();
-            let result = subscription.take(&mut *message).unwrap();
-            if result {
-                subscription.callback_fn(message);
-            }
+    for subscription in &entities.subscriptions {
+        let mut message = subscription.create_message();
+        if let Some(message_info) = subscription.take_with_info(&mut *message).unwrap() {
+            subscription.callback_fn(message, message_info);
+        }
+    }
+    for (index, guard_condition) in entities.guard_conditions.iter().enumerate() {
+        // rcl_wait clears the entries that weren't triggered
+        if !unsafe { *wait_set_handle.guard_conditions.add(index) }.is_null() {
+            guard_condition.callback_fn();
         }
     }
+    for timer in &entities.timers {
+        timer.call()?;
+    }
+    for (index, event) in entities.events.iter().enumerate() {
+        // Some middlewares report an event as taken even when it wasn't ready
+        if unsafe { *wait_set_handle.events.add(index) }.is_null() {
+            continue;
+        }
+        if let Some(status) = event.take()? {
+            event.callback_fn(status);
+        }
+    }
+
+    Ok(())
+}
+
+fn add_to_wait_set(entities: &WaitableEntities, wait_set_handle: &mut rcl_wait_set_t) -> RclResult {
     unsafe {
-        rcl_wait_set_fini(&mut wait_set_handle as *mut _).ok()?;
+        rcl_wait_set_clear(wait_set_handle as *mut _).ok()?;
+    }
+
+    for subscription in &entities.subscriptions {
+        let subscription_handle = &*subscription.handle().get();
+        unsafe {
+            rcl_wait_set_add_subscription(
+                wait_set_handle as *mut _,
+                subscription_handle as *const _,
+                std::ptr::null_mut(),
+            )
+            .ok()?;
+        }
+    }
+
+    for event in &entities.events {
+        let event_handle = &*event.get();
+        unsafe {
+            rcl_wait_set_add_event(
+                wait_set_handle as *mut _,
+                event_handle as *const _,
+                std::ptr::null_mut(),
+            )
+            .ok()?;
+        }
+    }
+
+    for guard_condition in &entities.guard_conditions {
+        let guard_condition_handle = &*guard_condition.get();
+        unsafe {
+            rcl_wait_set_add_guard_condition(
+                wait_set_handle as *mut _,
+                guard_condition_handle as *const _,
+                std::ptr::null_mut(),
+            )
+            .ok()?;
+        }
+    }
+
+    for timer in &entities.timers {
+        // Canceled timers neither wake the wait set nor get called
+        if timer.is_canceled()? {
+            continue;
+        }
+        let timer_handle = &*timer.get();
+        unsafe {
+            rcl_wait_set_add_timer(
+                wait_set_handle as *mut _,
+                timer_handle as *const _,
+                std::ptr::null_mut(),
+            )
+            .ok()?;
+        }
     }
 
     Ok(())
 }
+
+/// Whether `rcl_wait` left any entry in place, i.e. whether it actually found something ready
+fn wait_set_has_ready_entries(wait_set: &rcl_wait_set_t) -> bool {
+    unsafe fn any_set<T>(entries: *mut *const T, size: usize) -> bool {
+        !entries.is_null() && (0..size).any(|index| !(*entries.add(index)).is_null())
+    }
+    unsafe {
+        any_set(wait_set.subscriptions, wait_set.size_of_subscriptions)
+            || any_set(wait_set.guard_conditions, wait_set.size_of_guard_conditions)
+            || any_set(wait_set.timers, wait_set.size_of_timers)
+            || any_set(wait_set.events, wait_set.size_of_events)
+    }
+}
@@ -0,0 +1,85 @@
+use crate::error::{rcutils_ok, record_error_state, RclError, RclResult};
+use crate::{Handle, Node};
+use rcl_sys::*;
+use std::os::raw::c_int;
+
+/// Severity threshold of a logger, matching the rcutils levels
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogSeverity {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl From<LogSeverity> for RCUTILS_LOG_SEVERITY {
+    fn from(severity: LogSeverity) -> Self {
+        match severity {
+            LogSeverity::Debug => RCUTILS_LOG_SEVERITY::RCUTILS_LOG_SEVERITY_DEBUG,
+            LogSeverity::Info => RCUTILS_LOG_SEVERITY::RCUTILS_LOG_SEVERITY_INFO,
+            LogSeverity::Warn => RCUTILS_LOG_SEVERITY::RCUTILS_LOG_SEVERITY_WARN,
+            LogSeverity::Error => RCUTILS_LOG_SEVERITY::RCUTILS_LOG_SEVERITY_ERROR,
+            LogSeverity::Fatal => RCUTILS_LOG_SEVERITY::RCUTILS_LOG_SEVERITY_FATAL,
+        }
+    }
+}
+
+impl LogSeverity {
+    fn from_level(level: c_int) -> Option<Self> {
+        let severity = match level {
+            level if level == RCUTILS_LOG_SEVERITY::RCUTILS_LOG_SEVERITY_DEBUG as c_int => {
+                LogSeverity::Debug
+            }
+            level if level == RCUTILS_LOG_SEVERITY::RCUTILS_LOG_SEVERITY_INFO as c_int => {
+                LogSeverity::Info
+            }
+            level if level == RCUTILS_LOG_SEVERITY::RCUTILS_LOG_SEVERITY_WARN as c_int => {
+                LogSeverity::Warn
+            }
+            level if level == RCUTILS_LOG_SEVERITY::RCUTILS_LOG_SEVERITY_ERROR as c_int => {
+                LogSeverity::Error
+            }
+            level if level == RCUTILS_LOG_SEVERITY::RCUTILS_LOG_SEVERITY_FATAL as c_int => {
+                LogSeverity::Fatal
+            }
+            _ => return None,
+        };
+        Some(severity)
+    }
+}
+
+impl Node {
+    /// Sets the severity below which messages from this node's logger are dropped
+    pub fn set_logger_level(&self, severity: LogSeverity) -> RclResult {
+        let node_handle = &*self.handle.get();
+        unsafe {
+            let logger_name = rcl_node_get_logger_name(node_handle as *const _);
+            if logger_name.is_null() {
+                return Err(RclError::NodeInvalid);
+            }
+            rcutils_ok(rcutils_logging_set_logger_level(
+                logger_name,
+                RCUTILS_LOG_SEVERITY::from(severity) as c_int,
+            ))
+        }
+    }
+
+    /// Returns the level set for this node's logger, or `None` if it uses the default level
+    pub fn logger_level(&self) -> RclResult<Option<LogSeverity>> {
+        let node_handle = &*self.handle.get();
+        let level = unsafe {
+            let logger_name = rcl_node_get_logger_name(node_handle as *const _);
+            if logger_name.is_null() {
+                return Err(RclError::NodeInvalid);
+            }
+            rcutils_logging_get_logger_level(logger_name)
+        };
+
+        if level < 0 {
+            record_error_state();
+            return Err(RclError::Error);
+        }
+        Ok(LogSeverity::from_level(level))
+    }
+}
@@ -0,0 +1,173 @@
+use crate::error::{RclError, RclResult, ToRclResult};
+use crate::{Allocator, Context, Handle, Node, NodeHandle, ParameterValue};
+use rcl_sys::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::rc::Rc;
+
+/// Options a [`Node`] was created with, as reported by rcl
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeOptions {
+    pub use_global_arguments: bool,
+    pub enable_rosout: bool,
+    pub domain_id: usize,
+}
+
+/// Builder for a [`Node`] with non-default options
+///
+/// ```ignore
+/// let node = Node::builder("my_node", &context)
+///     .namespace("/my_ns")
+///     .use_global_arguments(false)
+///     .build()?;
+/// ```
+pub struct NodeBuilder<'a> {
+    context: &'a Context,
+    name: String,
+    namespace: String,
+    use_global_arguments: bool,
+    allocator: Rc<dyn Allocator>,
+    use_sim_time: bool,
+    ros_time_override: Option<i64>,
+    enable_rosout: bool,
+    parameter_overrides: HashMap<String, ParameterValue>,
+    automatically_declare_parameters_from_overrides: bool,
+}
+
+impl<'a> NodeBuilder<'a> {
+    pub fn new(name: &str, context: &'a Context) -> Self {
+        Self {
+            context,
+            name: name.to_owned(),
+            namespace: String::new(),
+            use_global_arguments: true,
+            allocator: context.allocator.clone(),
+            use_sim_time: false,
+            ros_time_override: None,
+            enable_rosout: true,
+            parameter_overrides: HashMap::new(),
+            automatically_declare_parameters_from_overrides: false,
+        }
+    }
+
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = namespace.to_owned();
+        self
+    }
+
+    /// Whether the node also applies the process-wide ROS arguments, e.g. remap rules
+    ///
+    /// Defaults to `true`, as in rcl. Disable it for a node that should only follow its own
+    /// arguments.
+    pub fn use_global_arguments(mut self, enable: bool) -> Self {
+        self.use_global_arguments = enable;
+        self
+    }
+
+    /// Whether the node publishes its log messages on `/rosout`
+    ///
+    /// Defaults to `true`, as in rcl.
+    pub fn enable_rosout(mut self, enable: bool) -> Self {
+        self.enable_rosout = enable;
+        self
+    }
+
+    /// Allocator for the node and the entities created from it
+    ///
+    /// Defaults to the allocator of the context.
+    pub fn allocator(mut self, allocator: Rc<dyn Allocator>) -> Self {
+        self.allocator = allocator;
+        self
+    }
+
+    /// Overrides the default value of a parameter, for when it is declared
+    ///
+    /// Takes precedence over parameter files, but `-p` command line arguments still take
+    /// precedence over this.
+    pub fn parameter_override(mut self, name: &str, value: ParameterValue) -> Self {
+        self.parameter_overrides.insert(name.to_owned(), value);
+        self
+    }
+
+    /// Whether every parameter that has an override, from [`NodeBuilder::parameter_override`],
+    /// a parameter file or a `-p` argument, is declared when the node is built
+    ///
+    /// The parameters get the type of their override. Defaults to `false`, so that only the
+    /// parameters the node declares itself exist, as in rclcpp.
+    pub fn automatically_declare_parameters_from_overrides(mut self, enable: bool) -> Self {
+        self.automatically_declare_parameters_from_overrides = enable;
+        self
+    }
+
+    /// Whether the node's clock follows simulated time published on `/clock`
+    ///
+    /// Defaults to `false`. This can be changed later with [`Node::set_use_sim_time`].
+    pub fn use_sim_time(mut self, enable: bool) -> Self {
+        self.use_sim_time = enable;
+        self
+    }
+
+    /// Starts the node's clock at `nanoseconds`, with the ROS time override enabled
+    ///
+    /// Meant for tests: time then only moves when set through [`Node::get_clock`] with
+    /// [`crate::Clock::set_ros_time_override`], and [`crate::ClockType::RosTime`] timers of the
+    /// node follow it. Simulated time also drives the override, so don't combine this with
+    /// [`NodeBuilder::use_sim_time`].
+    pub fn ros_time_override(mut self, nanoseconds: i64) -> Self {
+        self.ros_time_override = Some(nanoseconds);
+        self
+    }
+
+    pub fn build(self) -> RclResult<Node> {
+        // rcl validates the names, but can't be handed ones with a NUL byte inside
+        let raw_node_name = CString::new(self.name).map_err(|_| RclError::NodeInvalidName)?;
+        let raw_node_ns =
+            CString::new(self.namespace).map_err(|_| RclError::NodeInvalidNamespace)?;
+
+        let mut node_handle = unsafe { rcl_get_zero_initialized_node() };
+        let context_handle = &mut *self.context.handle.get_mut();
+
+        unsafe {
+            let mut node_options = rcl_node_get_default_options();
+            node_options.allocator = self.allocator.rcl_allocator();
+            node_options.use_global_arguments = self.use_global_arguments;
+            node_options.enable_rosout = self.enable_rosout;
+            rcl_node_init(
+                &mut node_handle as *mut _,
+                raw_node_name.as_ptr(),
+                raw_node_ns.as_ptr(),
+                context_handle as *mut _,
+                &node_options as *const _,
+            )
+            .ok()?;
+        }
+
+        let handle = Rc::new(NodeHandle {
+            handle: RefCell::new(node_handle),
+            owned: true,
+            _context: self.context.handle.clone(),
+        });
+        let node = Node::from_handle(
+            handle,
+            self.context,
+            self.allocator,
+            self.parameter_overrides,
+        )?;
+        node.set_use_sim_time(self.use_sim_time)?;
+        if let Some(nanoseconds) = self.ros_time_override {
+            let clock = node.get_clock();
+            clock.set_ros_time_override(nanoseconds)?;
+            clock.enable_ros_time_override()?;
+        }
+        if self.automatically_declare_parameters_from_overrides {
+            for (name, value) in node.get_parameter_overrides() {
+                // Nothing is declared yet, and the override determines the type, so this can't
+                // fail
+                let _ = node.declare_parameter(&name, value);
+            }
+        }
+
+        Ok(node)
+    }
+}
@@ -0,0 +1,188 @@
+use crate::error::{rcutils_ok, record_error_state, RclError, RclResult};
+use crate::qos::QoSProfile;
+use crate::{Node, PublisherHandle, SubscriptionHandle, SubscriptionOptions};
+use rcl_sys::*;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::rc::Rc;
+
+/// Message type support resolved at runtime from a package's `rosidl_typesupport_c` library
+///
+/// This backs [`DynamicPublisher`] and [`DynamicSubscription`], and can also back a hand-written
+/// `Message` impl for a package that isn't generated into the crate. The type support is valid
+/// for as long as the library is loaded, i.e. until this is dropped.
+///
+/// ```ignore
+/// let library = TypeSupportLibrary::load("std_msgs/msg/String")?;
+/// let type_support = library.type_support() as uintptr_t;
+/// ```
+pub struct TypeSupportLibrary {
+    library: RefCell<rcutils_shared_library_t>,
+    type_support: *const rosidl_message_type_support_t,
+}
+
+impl TypeSupportLibrary {
+    /// Loads the type support for a type name like `"std_msgs/msg/String"` or `"std_msgs/String"`
+    ///
+    /// Resolves `rosidl_typesupport_c__get_message_type_support_handle__<pkg>__msg__<Type>` from
+    /// `lib<pkg>__rosidl_typesupport_c`, which has to be on the library search path.
+    pub fn load(type_name: &str) -> RclResult<Self> {
+        let (package, subfolder, name) =
+            split_type_name(type_name).ok_or(RclError::InvalidArgument)?;
+
+        let library_name = CString::new(format!("{}__rosidl_typesupport_c", package))
+            .map_err(|_| RclError::InvalidArgument)?;
+        let symbol_name = CString::new(format!(
+            "rosidl_typesupport_c__get_message_type_support_handle__{}__{}__{}",
+            package, subfolder, name
+        ))
+        .map_err(|_| RclError::InvalidArgument)?;
+
+        let mut platform_library_name: Vec<c_char> = vec![0; 1024];
+        let mut library = unsafe { rcutils_get_zero_initialized_shared_library() };
+
+        unsafe {
+            rcutils_ok(rcutils_get_platform_library_name(
+                library_name.as_ptr(),
+                platform_library_name.as_mut_ptr(),
+                platform_library_name.len() as u32,
+                false,
+            ))?;
+            rcutils_ok(rcutils_load_shared_library(
+                &mut library as *mut _,
+                platform_library_name.as_ptr(),
+                rcutils_get_default_allocator(),
+            ))?;
+        }
+
+        let symbol = unsafe { rcutils_get_symbol(&library as *const _, symbol_name.as_ptr()) };
+        if symbol.is_null() {
+            record_error_state();
+            unsafe {
+                rcutils_unload_shared_library(&mut library as *mut _);
+            }
+            return Err(RclError::Error);
+        }
+
+        let type_support = unsafe {
+            let get_type_support: extern "C" fn() -> *const rosidl_message_type_support_t =
+                std::mem::transmute(symbol);
+            get_type_support()
+        };
+
+        Ok(Self {
+            library: RefCell::new(library),
+            type_support,
+        })
+    }
+
+    pub fn type_support(&self) -> *const rosidl_message_type_support_t {
+        self.type_support
+    }
+}
+
+impl Drop for TypeSupportLibrary {
+    fn drop(&mut self) {
+        let library = &mut *self.library.borrow_mut();
+        unsafe {
+            rcutils_unload_shared_library(library as *mut _);
+        }
+    }
+}
+
+fn split_type_name(type_name: &str) -> Option<(&str, &str, &str)> {
+    let parts: Vec<&str> = type_name.split('/').collect();
+    match parts.as_slice() {
+        [package, name] => Some((package, "msg", name)),
+        [package, subfolder, name] => Some((package, subfolder, name)),
+        _ => None,
+    }
+}
+
+/// Publisher for a message type only known at runtime, e.g. `"std_msgs/msg/String"`
+///
+/// Messages are published as buffers already serialized by the middleware.
+pub struct DynamicPublisher {
+    pub handle: Rc<PublisherHandle>,
+    // Declared after the handle, so the library is unloaded only once the publisher is gone
+    _type_support_library: TypeSupportLibrary,
+    type_name: String,
+}
+
+impl DynamicPublisher {
+    pub fn new(node: &Node, topic: &str, type_name: &str, qos: QoSProfile) -> RclResult<Self> {
+        let type_support_library = TypeSupportLibrary::load(type_name)?;
+        let handle = Rc::new(PublisherHandle::new(
+            node,
+            type_support_library.type_support(),
+            topic,
+            qos,
+        )?);
+        node.register_local_publisher(&handle, type_name.to_owned());
+
+        Ok(Self {
+            handle,
+            _type_support_library: type_support_library,
+            type_name: type_name.to_owned(),
+        })
+    }
+
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    /// Returns the fully qualified name of the topic, after remapping
+    pub fn topic_name(&self) -> String {
+        self.handle.topic_name()
+    }
+
+    pub fn publish_serialized(&self, buffer: &[u8]) -> RclResult {
+        self.handle.publish_serialized(buffer)
+    }
+}
+
+/// Subscription for a message type only known at runtime, e.g. `"std_msgs/msg/String"`
+///
+/// Messages are taken as buffers serialized by the middleware. Dynamic subscriptions have no
+/// callback, so they are not serviced by [`crate::spin`] and have to be polled instead.
+pub struct DynamicSubscription {
+    pub handle: Rc<SubscriptionHandle>,
+    // Declared after the handle, so the library is unloaded only once the subscription is gone
+    _type_support_library: TypeSupportLibrary,
+    type_name: String,
+}
+
+impl DynamicSubscription {
+    pub fn new(node: &Node, topic: &str, type_name: &str, qos: QoSProfile) -> RclResult<Self> {
+        let type_support_library = TypeSupportLibrary::load(type_name)?;
+        let handle = Rc::new(SubscriptionHandle::new(
+            node,
+            type_support_library.type_support(),
+            topic,
+            qos,
+            &SubscriptionOptions::default(),
+        )?);
+        node.register_local_subscription(&handle, type_name.to_owned());
+
+        Ok(Self {
+            handle,
+            _type_support_library: type_support_library,
+            type_name: type_name.to_owned(),
+        })
+    }
+
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    /// Returns the fully qualified name of the topic, after remapping
+    pub fn topic_name(&self) -> String {
+        self.handle.topic_name()
+    }
+
+    /// Returns `None` if no message was available
+    pub fn take_serialized(&self) -> RclResult<Option<Vec<u8>>> {
+        self.handle.take_serialized()
+    }
+}
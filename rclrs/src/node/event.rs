@@ -0,0 +1,285 @@
+use crate::error::{RclError, RclResult, ToRclResult};
+use crate::{Handle, Node, PublisherHandle, SubscriptionHandle};
+use rcl_sys::*;
+use std::cell::{Ref, RefCell, RefMut};
+use std::os::raw::c_void;
+use std::rc::{Rc, Weak};
+
+/// QoS events a publisher can report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PublisherEventType {
+    OfferedDeadlineMissed,
+    LivelinessLost,
+    OfferedIncompatibleQoS,
+}
+
+/// QoS events a subscription can report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubscriptionEventType {
+    RequestedDeadlineMissed,
+    LivelinessChanged,
+    RequestedIncompatibleQoS,
+}
+
+/// QoS policy that was found to be incompatible between a publisher and a subscription
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QoSPolicyKind {
+    Invalid,
+    Durability,
+    Deadline,
+    Liveliness,
+    Reliability,
+    History,
+    Lifespan,
+}
+
+impl From<rmw_qos_policy_kind_t> for QoSPolicyKind {
+    fn from(kind: rmw_qos_policy_kind_t) -> Self {
+        match kind {
+            rmw_qos_policy_kind_t::RMW_QOS_POLICY_DURABILITY => QoSPolicyKind::Durability,
+            rmw_qos_policy_kind_t::RMW_QOS_POLICY_DEADLINE => QoSPolicyKind::Deadline,
+            rmw_qos_policy_kind_t::RMW_QOS_POLICY_LIVELINESS => QoSPolicyKind::Liveliness,
+            rmw_qos_policy_kind_t::RMW_QOS_POLICY_RELIABILITY => QoSPolicyKind::Reliability,
+            rmw_qos_policy_kind_t::RMW_QOS_POLICY_HISTORY => QoSPolicyKind::History,
+            rmw_qos_policy_kind_t::RMW_QOS_POLICY_LIFESPAN => QoSPolicyKind::Lifespan,
+            _ => QoSPolicyKind::Invalid,
+        }
+    }
+}
+
+/// Status delivered with a QoS event
+///
+/// The `*_change` fields count the events since the status was last taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QoSEventStatus {
+    OfferedDeadlineMissed {
+        total_count: i32,
+        total_count_change: i32,
+    },
+    LivelinessLost {
+        total_count: i32,
+        total_count_change: i32,
+    },
+    OfferedIncompatibleQoS {
+        total_count: i32,
+        total_count_change: i32,
+        last_policy_kind: QoSPolicyKind,
+    },
+    RequestedDeadlineMissed {
+        total_count: i32,
+        total_count_change: i32,
+    },
+    LivelinessChanged {
+        alive_count: i32,
+        not_alive_count: i32,
+        alive_count_change: i32,
+        not_alive_count_change: i32,
+    },
+    RequestedIncompatibleQoS {
+        total_count: i32,
+        total_count_change: i32,
+        last_policy_kind: QoSPolicyKind,
+    },
+}
+
+enum EventSource {
+    Publisher(Rc<PublisherHandle>, PublisherEventType),
+    Subscription(Rc<SubscriptionHandle>, SubscriptionEventType),
+}
+
+/// A QoS event of a publisher or subscription, whose callback runs in [`crate::spin`]
+pub struct QoSEvent {
+    handle: RefCell<rcl_event_t>,
+    // Keeps the publisher or subscription alive, since the event has to be finalized first
+    source: EventSource,
+    callback: RefCell<Box<dyn FnMut(QoSEventStatus) + 'static>>,
+}
+
+impl QoSEvent {
+    fn new<F>(source: EventSource, callback: F) -> RclResult<Self>
+    where
+        F: FnMut(QoSEventStatus) + 'static,
+    {
+        let mut event_handle = unsafe { rcl_get_zero_initialized_event() };
+
+        unsafe {
+            match &source {
+                EventSource::Publisher(publisher, event_type) => {
+                    let publisher_handle = &*publisher.get();
+                    rcl_publisher_event_init(
+                        &mut event_handle as *mut _,
+                        publisher_handle as *const _,
+                        match event_type {
+                            PublisherEventType::OfferedDeadlineMissed => {
+                                rcl_publisher_event_type_t::RCL_PUBLISHER_OFFERED_DEADLINE_MISSED
+                            }
+                            PublisherEventType::LivelinessLost => {
+                                rcl_publisher_event_type_t::RCL_PUBLISHER_LIVELINESS_LOST
+                            }
+                            PublisherEventType::OfferedIncompatibleQoS => {
+                                rcl_publisher_event_type_t::RCL_PUBLISHER_OFFERED_INCOMPATIBLE_QOS
+                            }
+                        },
+                    )
+                }
+                EventSource::Subscription(subscription, event_type) => {
+                    let subscription_handle = &*subscription.get();
+                    rcl_subscription_event_init(
+                        &mut event_handle as *mut _,
+                        subscription_handle as *const _,
+                        match event_type {
+                            SubscriptionEventType::RequestedDeadlineMissed => {
+                                rcl_subscription_event_type_t::RCL_SUBSCRIPTION_REQUESTED_DEADLINE_MISSED
+                            }
+                            SubscriptionEventType::LivelinessChanged => {
+                                rcl_subscription_event_type_t::RCL_SUBSCRIPTION_LIVELINESS_CHANGED
+                            }
+                            SubscriptionEventType::RequestedIncompatibleQoS => {
+                                rcl_subscription_event_type_t::RCL_SUBSCRIPTION_REQUESTED_INCOMPATIBLE_QOS
+                            }
+                        },
+                    )
+                }
+            }
+            .ok()?;
+        }
+
+        Ok(Self {
+            handle: RefCell::new(event_handle),
+            source,
+            callback: RefCell::new(Box::new(callback)),
+        })
+    }
+
+    /// Takes the pending status of the event, or `None` if it didn't fire
+    pub fn take(&self) -> RclResult<Option<QoSEventStatus>> {
+        let status = match self.source {
+            EventSource::Publisher(_, PublisherEventType::OfferedDeadlineMissed) => self
+                .take_status::<rmw_offered_deadline_missed_status_t>()?
+                .map(|status| QoSEventStatus::OfferedDeadlineMissed {
+                    total_count: status.total_count,
+                    total_count_change: status.total_count_change,
+                }),
+            EventSource::Publisher(_, PublisherEventType::LivelinessLost) => self
+                .take_status::<rmw_liveliness_lost_status_t>()?
+                .map(|status| QoSEventStatus::LivelinessLost {
+                    total_count: status.total_count,
+                    total_count_change: status.total_count_change,
+                }),
+            EventSource::Publisher(_, PublisherEventType::OfferedIncompatibleQoS) => self
+                .take_status::<rmw_offered_qos_incompatible_event_status_t>()?
+                .map(|status| QoSEventStatus::OfferedIncompatibleQoS {
+                    total_count: status.total_count,
+                    total_count_change: status.total_count_change,
+                    last_policy_kind: status.last_policy_kind.into(),
+                }),
+            EventSource::Subscription(_, SubscriptionEventType::RequestedDeadlineMissed) => self
+                .take_status::<rmw_requested_deadline_missed_status_t>()?
+                .map(|status| QoSEventStatus::RequestedDeadlineMissed {
+                    total_count: status.total_count,
+                    total_count_change: status.total_count_change,
+                }),
+            EventSource::Subscription(_, SubscriptionEventType::LivelinessChanged) => self
+                .take_status::<rmw_liveliness_changed_status_t>()?
+                .map(|status| QoSEventStatus::LivelinessChanged {
+                    alive_count: status.alive_count,
+                    not_alive_count: status.not_alive_count,
+                    alive_count_change: status.alive_count_change,
+                    not_alive_count_change: status.not_alive_count_change,
+                }),
+            EventSource::Subscription(_, SubscriptionEventType::RequestedIncompatibleQoS) => self
+                .take_status::<rmw_requested_qos_incompatible_event_status_t>()?
+                .map(|status| QoSEventStatus::RequestedIncompatibleQoS {
+                    total_count: status.total_count,
+                    total_count_change: status.total_count_change,
+                    last_policy_kind: status.last_policy_kind.into(),
+                }),
+        };
+        Ok(status)
+    }
+
+    /// `S` has to be the rmw status struct matching the event type
+    fn take_status<S>(&self) -> RclResult<Option<S>> {
+        let handle = &*self.get();
+        let mut status = unsafe { std::mem::zeroed::<S>() };
+
+        let result =
+            unsafe { rcl_take_event(handle as *const _, &mut status as *mut S as *mut c_void) };
+
+        match result.into() {
+            RclError::Ok => Ok(Some(status)),
+            RclError::EventTakeFailed => Ok(None),
+            error => Err(error),
+        }
+    }
+
+    pub(crate) fn callback_fn(&self, status: QoSEventStatus) {
+        (*self.callback.borrow_mut())(status);
+    }
+}
+
+impl<'a> Handle<rcl_event_t> for &'a QoSEvent {
+    type DerefT = Ref<'a, rcl_event_t>;
+    type DerefMutT = RefMut<'a, rcl_event_t>;
+
+    fn get(self) -> Self::DerefT {
+        self.handle.borrow()
+    }
+
+    fn get_mut(self) -> Self::DerefMutT {
+        self.handle.borrow_mut()
+    }
+}
+
+impl Drop for QoSEvent {
+    fn drop(&mut self) {
+        let handle = &mut *self.get_mut();
+        unsafe {
+            rcl_event_fini(handle as *mut _);
+        }
+    }
+}
+
+impl Node {
+    /// Registers `callback` for a QoS event of the publisher owning `publisher_handle`
+    ///
+    /// The callback runs in [`crate::spin`] for as long as the returned event is kept alive.
+    pub fn create_publisher_event<F>(
+        &self,
+        publisher_handle: &Rc<PublisherHandle>,
+        event_type: PublisherEventType,
+        callback: F,
+    ) -> RclResult<Rc<QoSEvent>>
+    where
+        F: FnMut(QoSEventStatus) + 'static,
+    {
+        self.ensure_valid()?;
+        let source = EventSource::Publisher(publisher_handle.clone(), event_type);
+        self.add_event(QoSEvent::new(source, callback)?)
+    }
+
+    /// Registers `callback` for a QoS event of the subscription owning `subscription_handle`
+    ///
+    /// The callback runs in [`crate::spin`] for as long as the returned event is kept alive.
+    pub fn create_subscription_event<F>(
+        &self,
+        subscription_handle: &Rc<SubscriptionHandle>,
+        event_type: SubscriptionEventType,
+        callback: F,
+    ) -> RclResult<Rc<QoSEvent>>
+    where
+        F: FnMut(QoSEventStatus) + 'static,
+    {
+        self.ensure_valid()?;
+        let source = EventSource::Subscription(subscription_handle.clone(), event_type);
+        self.add_event(QoSEvent::new(source, callback)?)
+    }
+
+    fn add_event(&self, event: QoSEvent) -> RclResult<Rc<QoSEvent>> {
+        let event = Rc::new(event);
+        self.events
+            .borrow_mut()
+            .push(Rc::downgrade(&event) as Weak<QoSEvent>);
+        Ok(event)
+    }
+}
@@ -0,0 +1,634 @@
+use crate::error::{RclError, RclResult, ToRclResult};
+use crate::qos::QoSProfile;
+use crate::{Allocator, Handle, Node, NodeHandle};
+use rcl_sys::*;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::time::{Duration, Instant};
+
+/// Topics known to the graph, mapped to the message types they carry
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TopicNamesAndTypes(HashMap<String, Vec<String>>);
+
+impl TopicNamesAndTypes {
+    /// Returns the message types on `topic`, or `None` if the topic is unknown
+    pub fn types_for(&self, topic: &str) -> Option<&[String]> {
+        self.0.get(topic).map(Vec::as_slice)
+    }
+
+    pub fn contains(&self, topic: &str) -> bool {
+        self.0.contains_key(topic)
+    }
+
+    pub fn topics(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.0
+            .iter()
+            .map(|(topic, types)| (topic.as_str(), types.as_slice()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl IntoIterator for TopicNamesAndTypes {
+    type Item = (String, Vec<String>);
+    type IntoIter = std::collections::hash_map::IntoIter<String, Vec<String>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+type EndpointInfoByTopicFn = unsafe extern "C" fn(
+    *const rcl_node_t,
+    *mut rcl_allocator_t,
+    *const c_char,
+    bool,
+    *mut rcl_topic_endpoint_info_array_t,
+) -> rcl_ret_t;
+
+/// Whether a topic endpoint publishes or subscribes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndpointType {
+    Invalid,
+    Publisher,
+    Subscription,
+}
+
+impl From<rmw_endpoint_type_t> for EndpointType {
+    fn from(endpoint_type: rmw_endpoint_type_t) -> Self {
+        match endpoint_type {
+            rmw_endpoint_type_t::RMW_ENDPOINT_PUBLISHER => EndpointType::Publisher,
+            rmw_endpoint_type_t::RMW_ENDPOINT_SUBSCRIPTION => EndpointType::Subscription,
+            _ => EndpointType::Invalid,
+        }
+    }
+}
+
+/// A single publisher or subscription on a topic, as reported by the middleware
+pub struct TopicEndpointInfo {
+    pub node_name: String,
+    pub node_namespace: String,
+    pub topic_type: String,
+    pub endpoint_type: EndpointType,
+    pub endpoint_gid: [u8; RMW_GID_STORAGE_SIZE as usize],
+    pub qos: QoSProfile,
+}
+
+impl From<&rmw_topic_endpoint_info_t> for TopicEndpointInfo {
+    fn from(info: &rmw_topic_endpoint_info_t) -> Self {
+        let to_string = |string| {
+            unsafe { CStr::from_ptr(string) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        Self {
+            node_name: to_string(info.node_name),
+            node_namespace: to_string(info.node_namespace),
+            topic_type: to_string(info.topic_type),
+            endpoint_type: info.endpoint_type.into(),
+            endpoint_gid: info.endpoint_gid,
+            qos: (&info.qos_profile).into(),
+        }
+    }
+}
+
+/// The guard condition a node triggers whenever the graph changes
+///
+/// It is owned by the node, which the lifetime ties it to, and is only read by rcl, so add it
+/// to a wait set with `rcl_wait_set_add_guard_condition` but don't trigger or finalize it.
+#[derive(Clone, Copy)]
+pub struct GraphGuardCondition<'node> {
+    handle: *const rcl_guard_condition_t,
+    _node: PhantomData<&'node Node>,
+}
+
+impl<'node> GraphGuardCondition<'node> {
+    pub fn rcl_handle(&self) -> *const rcl_guard_condition_t {
+        self.handle
+    }
+}
+
+type NamesAndTypesByNodeFn = unsafe extern "C" fn(
+    *const rcl_node_t,
+    *mut rcl_allocator_t,
+    bool,
+    *const c_char,
+    *const c_char,
+    *mut rcl_names_and_types_t,
+) -> rcl_ret_t;
+
+type ServiceNamesAndTypesByNodeFn = unsafe extern "C" fn(
+    *const rcl_node_t,
+    *mut rcl_allocator_t,
+    *const c_char,
+    *const c_char,
+    *mut rcl_names_and_types_t,
+) -> rcl_ret_t;
+
+impl Node {
+    /// Returns the guard condition that wakes a wait set on any change to the graph
+    pub fn graph_guard_condition(&self) -> GraphGuardCondition<'_> {
+        let node_handle = &*self.handle.get();
+        GraphGuardCondition {
+            handle: unsafe { rcl_node_get_graph_guard_condition(node_handle as *const _) },
+            _node: PhantomData,
+        }
+    }
+
+    /// Returns all topics in the graph with their message types
+    ///
+    /// With `no_demangle`, topic names are reported as the middleware sees them, which includes
+    /// hidden and non-ROS topics, e.g. `rt/chatter` instead of `/chatter`.
+    pub fn get_topic_names_and_types(&self, no_demangle: bool) -> RclResult<TopicNamesAndTypes> {
+        let node_handle = &*self.handle.get();
+        let mut names_and_types = unsafe { rmw_get_zero_initialized_names_and_types() };
+        let mut allocator = self.allocator.rcl_allocator();
+
+        let result = unsafe {
+            rcl_get_topic_names_and_types(
+                node_handle as *const _,
+                &mut allocator as *mut _,
+                no_demangle,
+                &mut names_and_types as *mut _,
+            )
+        }
+        .ok()
+        .map(|_| TopicNamesAndTypes(names_and_types_to_map(&names_and_types)));
+
+        // rcl may have allocated part of the names and types even if the query failed
+        unsafe {
+            rcl_names_and_types_fini(&mut names_and_types as *mut _).ok()?;
+        }
+
+        result
+    }
+
+    /// Returns the topics published by the node with the given name and namespace,
+    /// mapped to the message types they carry
+    pub fn get_publisher_names_and_types_by_node(
+        &self,
+        node_name: &str,
+        node_namespace: &str,
+        demangle: bool,
+    ) -> RclResult<HashMap<String, Vec<String>>> {
+        self.get_names_and_types_by_node(
+            node_name,
+            node_namespace,
+            demangle,
+            rcl_get_publisher_names_and_types_by_node,
+        )
+    }
+
+    /// Returns the topics subscribed to by the node with the given name and namespace,
+    /// mapped to the message types they carry
+    pub fn get_subscriber_names_and_types_by_node(
+        &self,
+        node_name: &str,
+        node_namespace: &str,
+        demangle: bool,
+    ) -> RclResult<HashMap<String, Vec<String>>> {
+        self.get_names_and_types_by_node(
+            node_name,
+            node_namespace,
+            demangle,
+            rcl_get_subscriber_names_and_types_by_node,
+        )
+    }
+
+    /// Returns the services offered by the node with the given name and namespace,
+    /// mapped to the service types they use
+    ///
+    /// Fails with [`RclError::NodeNameNonExistent`] if there is no such node in the graph.
+    pub fn get_service_names_and_types_by_node(
+        &self,
+        node_name: &str,
+        node_namespace: &str,
+    ) -> RclResult<HashMap<String, Vec<String>>> {
+        self.get_service_names_and_types_by_node_with(
+            node_name,
+            node_namespace,
+            rcl_get_service_names_and_types_by_node,
+        )
+    }
+
+    /// Returns the services the node with the given name and namespace has clients for,
+    /// mapped to the service types they use
+    ///
+    /// Fails with [`RclError::NodeNameNonExistent`] if there is no such node in the graph.
+    pub fn get_client_names_and_types_by_node(
+        &self,
+        node_name: &str,
+        node_namespace: &str,
+    ) -> RclResult<HashMap<String, Vec<String>>> {
+        self.get_service_names_and_types_by_node_with(
+            node_name,
+            node_namespace,
+            rcl_get_client_names_and_types_by_node,
+        )
+    }
+
+    /// Returns the node, type, GID and QoS of every publisher on the given topic
+    pub fn get_publishers_info_by_topic(
+        &self,
+        topic: &str,
+        no_mangle: bool,
+    ) -> RclResult<Vec<TopicEndpointInfo>> {
+        self.get_endpoint_info_by_topic(topic, no_mangle, rcl_get_publishers_info_by_topic)
+    }
+
+    /// Returns the node, type, GID and QoS of every subscription on the given topic
+    pub fn get_subscriptions_info_by_topic(
+        &self,
+        topic: &str,
+        no_mangle: bool,
+    ) -> RclResult<Vec<TopicEndpointInfo>> {
+        self.get_endpoint_info_by_topic(topic, no_mangle, rcl_get_subscriptions_info_by_topic)
+    }
+
+    /// Returns the number of publishers on the given topic
+    pub fn count_publishers(&self, topic: &str) -> RclResult<usize> {
+        let topic_c_string = CString::new(topic).map_err(|_| RclError::TopicNameInvalid)?;
+        let node_handle = &*self.handle.get();
+        let mut count = 0;
+
+        unsafe {
+            rcl_count_publishers(
+                node_handle as *const _,
+                topic_c_string.as_ptr(),
+                &mut count as *mut _,
+            )
+            .ok()?;
+        }
+
+        Ok(count)
+    }
+
+    /// Returns the number of subscriptions on the given topic
+    pub fn count_subscribers(&self, topic: &str) -> RclResult<usize> {
+        let topic_c_string = CString::new(topic).map_err(|_| RclError::TopicNameInvalid)?;
+        let node_handle = &*self.handle.get();
+        let mut count = 0;
+
+        unsafe {
+            rcl_count_subscribers(
+                node_handle as *const _,
+                topic_c_string.as_ptr(),
+                &mut count as *mut _,
+            )
+            .ok()?;
+        }
+
+        Ok(count)
+    }
+
+    /// Returns the number of servers for the given fully qualified service name
+    ///
+    /// rcl has no such count on this distro, so this counts the nodes in the graph that offer
+    /// the service, which takes a query per node.
+    pub fn count_services(&self, service_name: &str) -> RclResult<usize> {
+        self.count_nodes_with_service(service_name, rcl_get_service_names_and_types_by_node)
+    }
+
+    /// Returns the number of clients for the given fully qualified service name
+    ///
+    /// Like [`Node::count_services`], this counts the nodes with a client for the service.
+    pub fn count_clients(&self, service_name: &str) -> RclResult<usize> {
+        self.count_nodes_with_service(service_name, rcl_get_client_names_and_types_by_node)
+    }
+
+    /// Blocks until at least one publisher exists on `topic`, or `timeout` elapses
+    ///
+    /// Instead of busy polling, this waits on the node's graph guard condition, so the
+    /// publisher count is only re-checked when discovery reports a change.
+    /// Returns whether a publisher appeared before the timeout.
+    pub fn wait_for_topic(&self, topic: &str, timeout: Duration) -> RclResult<bool> {
+        wait_for_graph_condition(&self.handle, &*self.allocator, timeout, || {
+            Ok(self.count_publishers(topic)? > 0)
+        })
+    }
+
+    /// Blocks until the graph changes in any way, e.g. a node, publisher or subscription is
+    /// discovered or goes away, or `timeout` elapses
+    ///
+    /// Returns whether the graph changed before the timeout, so tools can refresh their view of
+    /// the graph only when needed.
+    pub fn wait_for_graph_change(&self, timeout: Duration) -> RclResult<bool> {
+        wait_on_graph_guard_condition(&self.handle, &*self.allocator, timeout, Ok)
+    }
+
+    fn get_endpoint_info_by_topic(
+        &self,
+        topic: &str,
+        no_mangle: bool,
+        getter: EndpointInfoByTopicFn,
+    ) -> RclResult<Vec<TopicEndpointInfo>> {
+        let topic_c_string = CString::new(topic).map_err(|_| RclError::TopicNameInvalid)?;
+        let node_handle = &*self.handle.get();
+
+        let mut info_array = unsafe { rmw_get_zero_initialized_topic_endpoint_info_array() };
+        let mut allocator = self.allocator.rcl_allocator();
+
+        let result = unsafe {
+            getter(
+                node_handle as *const _,
+                &mut allocator as *mut _,
+                topic_c_string.as_ptr(),
+                no_mangle,
+                &mut info_array as *mut _,
+            )
+        }
+        .ok()
+        .map(|_| {
+            if info_array.size == 0 {
+                return vec![];
+            }
+            unsafe { std::slice::from_raw_parts(info_array.info_array, info_array.size) }
+                .iter()
+                .map(TopicEndpointInfo::from)
+                .collect()
+        });
+
+        // Like the names and types, the array may be partly allocated even if the query failed
+        unsafe {
+            rmw_topic_endpoint_info_array_fini(&mut info_array as *mut _, &mut allocator as *mut _)
+                .ok()?;
+        }
+
+        result
+    }
+
+    fn get_names_and_types_by_node(
+        &self,
+        node_name: &str,
+        node_namespace: &str,
+        demangle: bool,
+        getter: NamesAndTypesByNodeFn,
+    ) -> RclResult<HashMap<String, Vec<String>>> {
+        let node_name = CString::new(node_name).map_err(|_| RclError::NodeInvalidName)?;
+        let node_namespace =
+            CString::new(node_namespace).map_err(|_| RclError::NodeInvalidNamespace)?;
+        let node_handle = &*self.handle.get();
+
+        let mut names_and_types = unsafe { rmw_get_zero_initialized_names_and_types() };
+        let mut allocator = self.allocator.rcl_allocator();
+
+        let result = unsafe {
+            getter(
+                node_handle as *const _,
+                &mut allocator as *mut _,
+                !demangle,
+                node_name.as_ptr(),
+                node_namespace.as_ptr(),
+                &mut names_and_types as *mut _,
+            )
+        }
+        .ok()
+        .map(|_| names_and_types_to_map(&names_and_types));
+
+        // rcl may have allocated part of the names and types even if the query failed
+        unsafe {
+            rcl_names_and_types_fini(&mut names_and_types as *mut _).ok()?;
+        }
+
+        result
+    }
+
+    fn count_nodes_with_service(
+        &self,
+        service_name: &str,
+        getter: ServiceNamesAndTypesByNodeFn,
+    ) -> RclResult<usize> {
+        if service_name.contains('\0') {
+            return Err(RclError::ServiceNameInvalid);
+        }
+
+        let mut count = 0;
+        for (node_name, node_namespace) in self.get_node_names_and_namespaces()? {
+            match self.get_service_names_and_types_by_node_with(&node_name, &node_namespace, getter)
+            {
+                Ok(services) if services.contains_key(service_name) => count += 1,
+                Ok(_) => {}
+                // The node left the graph after it was listed
+                Err(RclError::NodeNameNonExistent) => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(count)
+    }
+
+    fn get_node_names_and_namespaces(&self) -> RclResult<Vec<(String, String)>> {
+        let node_handle = &*self.handle.get();
+        let mut node_names = unsafe { rcutils_get_zero_initialized_string_array() };
+        let mut node_namespaces = unsafe { rcutils_get_zero_initialized_string_array() };
+
+        let result = unsafe {
+            rcl_get_node_names(
+                node_handle as *const _,
+                self.allocator.rcl_allocator(),
+                &mut node_names as *mut _,
+                &mut node_namespaces as *mut _,
+            )
+        }
+        .ok()
+        .map(|_| {
+            string_array_to_vec(&node_names)
+                .into_iter()
+                .zip(string_array_to_vec(&node_namespaces))
+                .collect()
+        });
+
+        unsafe {
+            rcutils_string_array_fini(&mut node_names as *mut _).ok()?;
+            rcutils_string_array_fini(&mut node_namespaces as *mut _).ok()?;
+        }
+
+        result
+    }
+
+    fn get_service_names_and_types_by_node_with(
+        &self,
+        node_name: &str,
+        node_namespace: &str,
+        getter: ServiceNamesAndTypesByNodeFn,
+    ) -> RclResult<HashMap<String, Vec<String>>> {
+        let node_name = CString::new(node_name).map_err(|_| RclError::NodeInvalidName)?;
+        let node_namespace =
+            CString::new(node_namespace).map_err(|_| RclError::NodeInvalidNamespace)?;
+        let node_handle = &*self.handle.get();
+
+        let mut names_and_types = unsafe { rmw_get_zero_initialized_names_and_types() };
+        let mut allocator = self.allocator.rcl_allocator();
+
+        let result = unsafe {
+            getter(
+                node_handle as *const _,
+                &mut allocator as *mut _,
+                node_name.as_ptr(),
+                node_namespace.as_ptr(),
+                &mut names_and_types as *mut _,
+            )
+        }
+        .ok()
+        .map(|_| names_and_types_to_map(&names_and_types));
+
+        // rcl may have allocated part of the names and types even if the query failed
+        unsafe {
+            rcl_names_and_types_fini(&mut names_and_types as *mut _).ok()?;
+        }
+
+        result
+    }
+}
+
+/// Copies the contents of an `rcl_names_and_types_t` into owned Rust strings
+pub(crate) fn names_and_types_to_map(
+    names_and_types: &rcl_names_and_types_t,
+) -> HashMap<String, Vec<String>> {
+    let names = string_array_to_vec(&names_and_types.names);
+    if names.is_empty() {
+        return HashMap::new();
+    }
+
+    let types = unsafe { std::slice::from_raw_parts(names_and_types.types, names.len()) };
+    names
+        .into_iter()
+        .zip(types.iter().map(string_array_to_vec))
+        .collect()
+}
+
+fn string_array_to_vec(array: &rcutils_string_array_t) -> Vec<String> {
+    if array.size == 0 {
+        return vec![];
+    }
+
+    let data = unsafe { std::slice::from_raw_parts(array.data, array.size) };
+    data.iter()
+        .map(|&string| {
+            unsafe { CStr::from_ptr(string) }
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+/// Longest time [`wait_for_graph_condition`] goes without checking the condition
+///
+/// The middleware may only match endpoints after the graph change that announced them, so
+/// waiting for graph changes alone could miss the condition becoming true.
+const GRAPH_CONDITION_POLL_PERIOD: Duration = Duration::from_millis(50);
+
+/// Waits on the graph guard condition of a node until `condition` holds
+///
+/// `condition` is evaluated once up front and again after every graph change, until it
+/// returns `true` or `timeout` elapses. This takes the node handle instead of the node so that
+/// publishers and subscriptions can wait as well.
+pub(crate) fn wait_for_graph_condition<F>(
+    node_handle: &NodeHandle,
+    allocator: &dyn Allocator,
+    timeout: Duration,
+    mut condition: F,
+) -> RclResult<bool>
+where
+    F: FnMut() -> RclResult<bool>,
+{
+    if condition()? {
+        return Ok(true);
+    }
+    wait_on_graph_guard_condition(node_handle, allocator, timeout, |_| condition())
+}
+
+/// Waits on the graph guard condition of a node until `on_wake` returns `true` or `timeout`
+/// elapses
+///
+/// `on_wake` is called whenever the wait returns, with whether the guard condition was
+/// triggered. The wait is woken up regularly besides, as some changes only show in the graph
+/// queries shortly after the guard condition was triggered.
+fn wait_on_graph_guard_condition<F>(
+    node_handle: &NodeHandle,
+    allocator: &dyn Allocator,
+    timeout: Duration,
+    mut on_wake: F,
+) -> RclResult<bool>
+where
+    F: FnMut(bool) -> RclResult<bool>,
+{
+    let deadline = Instant::now() + timeout;
+    let mut wait_set_handle = unsafe { rcl_get_zero_initialized_wait_set() };
+
+    let graph_guard_condition = {
+        let node_handle = &*node_handle.get();
+        unsafe {
+            rcl_wait_set_init(
+                &mut wait_set_handle as *mut _,
+                0,
+                1,
+                0,
+                0,
+                0,
+                0,
+                node_handle.context,
+                allocator.rcl_allocator(),
+            )
+            .ok()?;
+            rcl_node_get_graph_guard_condition(node_handle as *const _)
+        }
+    };
+
+    let result = loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break Ok(false);
+        }
+        let remaining = (deadline - now).min(GRAPH_CONDITION_POLL_PERIOD);
+        let remaining = i64::try_from(remaining.as_nanos()).unwrap_or(i64::MAX);
+
+        let wait_result = unsafe {
+            rcl_wait_set_clear(&mut wait_set_handle as *mut _)
+                .ok()
+                .and_then(|_| {
+                    rcl_wait_set_add_guard_condition(
+                        &mut wait_set_handle as *mut _,
+                        graph_guard_condition,
+                        std::ptr::null_mut(),
+                    )
+                    .ok()
+                })
+                .and_then(|_| rcl_wait(&mut wait_set_handle as *mut _, remaining).ok())
+        };
+
+        // The guard condition is the only entity in the wait set, so it's what was ready
+        let changed = match wait_result {
+            Ok(()) => Ok(true),
+            Err(RclError::Timeout) => Ok(false),
+            Err(error) => Err(error),
+        };
+        match changed {
+            Ok(changed) => match on_wake(changed) {
+                Ok(true) => break Ok(true),
+                Ok(false) => continue,
+                Err(error) => break Err(error),
+            },
+            Err(error) => break Err(error),
+        }
+    };
+
+    unsafe {
+        rcl_wait_set_fini(&mut wait_set_handle as *mut _).ok()?;
+    }
+
+    result
+}
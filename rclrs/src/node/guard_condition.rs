@@ -0,0 +1,101 @@
+use crate::error::{RclResult, ToRclResult};
+use crate::{ContextHandle, Handle, Node};
+use rcl_sys::*;
+use std::cell::{Ref, RefCell, RefMut};
+use std::rc::Rc;
+
+/// Wakes up [`crate::spin_once`] when triggered
+///
+/// The wait set is rebuilt on every call of [`crate::spin_once`], so entities created while
+/// spinning are picked up on the next wake-up. Triggering a guard condition makes that happen
+/// right away instead of after the current wait times out.
+pub struct GuardCondition {
+    handle: RefCell<rcl_guard_condition_t>,
+    // rcl keeps a pointer to the context, so it has to outlive the guard condition
+    _context: Rc<ContextHandle>,
+    callback: RefCell<Box<dyn FnMut()>>,
+}
+
+impl GuardCondition {
+    pub(crate) fn new<F>(node: &Node, callback: F) -> RclResult<Self>
+    where
+        F: FnMut() + 'static,
+    {
+        node.ensure_valid()?;
+        let mut guard_condition_handle = unsafe { rcl_get_zero_initialized_guard_condition() };
+
+        {
+            let context_handle = &mut *node.context.get_mut();
+            unsafe {
+                let mut options = rcl_guard_condition_get_default_options();
+                options.allocator = node.allocator.rcl_allocator();
+                rcl_guard_condition_init(
+                    &mut guard_condition_handle as *mut _,
+                    context_handle as *mut _,
+                    options,
+                )
+                .ok()?;
+            }
+        }
+
+        Ok(Self {
+            handle: RefCell::new(guard_condition_handle),
+            _context: node.context.clone(),
+            callback: RefCell::new(Box::new(callback)),
+        })
+    }
+
+    /// Wakes up the wait of the node's spin, which then runs the callback
+    pub fn trigger(&self) -> RclResult {
+        let handle = &mut *self.get_mut();
+        unsafe { rcl_trigger_guard_condition(handle as *mut _).ok() }
+    }
+
+    pub(crate) fn callback_fn(&self) {
+        (*self.callback.borrow_mut())();
+    }
+}
+
+impl<'a> Handle<rcl_guard_condition_t> for &'a GuardCondition {
+    type DerefT = Ref<'a, rcl_guard_condition_t>;
+    type DerefMutT = RefMut<'a, rcl_guard_condition_t>;
+
+    fn get(self) -> Self::DerefT {
+        self.handle.borrow()
+    }
+
+    fn get_mut(self) -> Self::DerefMutT {
+        self.handle.borrow_mut()
+    }
+}
+
+impl Drop for GuardCondition {
+    fn drop(&mut self) {
+        let handle = &mut *self.get_mut();
+        unsafe {
+            rcl_guard_condition_fini(handle as *mut _).unwrap_in_drop("rcl_guard_condition_fini");
+        }
+    }
+}
+
+impl Node {
+    /// Creates a guard condition that wakes up the node's spin without running anything
+    pub fn create_guard_condition(&self) -> RclResult<Rc<GuardCondition>> {
+        self.create_guard_condition_with_callback(|| {})
+    }
+
+    /// Creates a guard condition that runs `callback` in the node's spin after being triggered
+    pub fn create_guard_condition_with_callback<F>(
+        &self,
+        callback: F,
+    ) -> RclResult<Rc<GuardCondition>>
+    where
+        F: FnMut() + 'static,
+    {
+        let guard_condition = Rc::new(GuardCondition::new(self, callback)?);
+        self.guard_conditions
+            .borrow_mut()
+            .push(Rc::downgrade(&guard_condition));
+        Ok(guard_condition)
+    }
+}
@@ -0,0 +1,389 @@
+use crate::error::{RclResult, ToRclResult};
+use crate::{Context, Handle, Node};
+use rcl_sys::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::ops::{Deref, DerefMut};
+use std::os::raw::c_char;
+
+#[link(name = "lifecycle_msgs__rosidl_typesupport_c")]
+extern "C" {
+    fn rosidl_typesupport_c__get_message_type_support_handle__lifecycle_msgs__msg__TransitionEvent(
+    ) -> *const rosidl_message_type_support_t;
+    fn rosidl_typesupport_c__get_service_type_support_handle__lifecycle_msgs__srv__ChangeState(
+    ) -> *const rosidl_service_type_support_t;
+    fn rosidl_typesupport_c__get_service_type_support_handle__lifecycle_msgs__srv__GetState(
+    ) -> *const rosidl_service_type_support_t;
+    fn rosidl_typesupport_c__get_service_type_support_handle__lifecycle_msgs__srv__GetAvailableStates(
+    ) -> *const rosidl_service_type_support_t;
+    fn rosidl_typesupport_c__get_service_type_support_handle__lifecycle_msgs__srv__GetAvailableTransitions(
+    ) -> *const rosidl_service_type_support_t;
+}
+
+/// States of the default lifecycle state machine, with the ids from `lifecycle_msgs/msg/State`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LifecycleState {
+    Unknown = 0,
+    Unconfigured = 1,
+    Inactive = 2,
+    Active = 3,
+    Finalized = 4,
+    Configuring = 10,
+    CleaningUp = 11,
+    ShuttingDown = 12,
+    Activating = 13,
+    Deactivating = 14,
+    ErrorProcessing = 15,
+}
+
+impl From<u32> for LifecycleState {
+    fn from(id: u32) -> Self {
+        match id {
+            1 => LifecycleState::Unconfigured,
+            2 => LifecycleState::Inactive,
+            3 => LifecycleState::Active,
+            4 => LifecycleState::Finalized,
+            10 => LifecycleState::Configuring,
+            11 => LifecycleState::CleaningUp,
+            12 => LifecycleState::ShuttingDown,
+            13 => LifecycleState::Activating,
+            14 => LifecycleState::Deactivating,
+            15 => LifecycleState::ErrorProcessing,
+            _ => LifecycleState::Unknown,
+        }
+    }
+}
+
+/// Transitions a user can request, with the ids from `lifecycle_msgs/msg/Transition`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LifecycleTransition {
+    Configure = 1,
+    Cleanup = 2,
+    Activate = 3,
+    Deactivate = 4,
+    UnconfiguredShutdown = 5,
+    InactiveShutdown = 6,
+    ActiveShutdown = 7,
+}
+
+impl LifecycleTransition {
+    /// The shutdown transition that leaves `state`
+    fn shutdown_from(state: LifecycleState) -> Self {
+        match state {
+            LifecycleState::Inactive => LifecycleTransition::InactiveShutdown,
+            LifecycleState::Active => LifecycleTransition::ActiveShutdown,
+            _ => LifecycleTransition::UnconfiguredShutdown,
+        }
+    }
+}
+
+/// Value returned by a transition callback to decide which state the node ends up in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TransitionCallbackReturn {
+    Success,
+    Failure,
+    Error,
+}
+
+impl TransitionCallbackReturn {
+    fn label(self) -> *const c_char {
+        unsafe {
+            match self {
+                TransitionCallbackReturn::Success => rcl_lifecycle_transition_success_label,
+                TransitionCallbackReturn::Failure => rcl_lifecycle_transition_failure_label,
+                TransitionCallbackReturn::Error => rcl_lifecycle_transition_error_label,
+            }
+        }
+    }
+}
+
+type TransitionCallback = Box<dyn FnMut() -> TransitionCallbackReturn + 'static>;
+
+/// A managed node following the ROS 2 lifecycle state machine
+///
+/// Transitions are triggered in-process through [`LifecycleNode::trigger_transition`] or its
+/// shorthands. rcl publishes a transition event for each of them on `~/transition_event`.
+/// The change-state/get-state services are created by rcl, but not yet served.
+pub struct LifecycleNode {
+    node: Node,
+    state_machine: RefCell<rcl_lifecycle_state_machine_t>,
+    callbacks: RefCell<HashMap<LifecycleState, TransitionCallback>>,
+}
+
+impl LifecycleNode {
+    pub fn new(node_name: &str, context: &Context) -> RclResult<Self> {
+        Self::new_with_namespace(node_name, "", context)
+    }
+
+    pub fn new_with_namespace(
+        node_name: &str,
+        node_ns: &str,
+        context: &Context,
+    ) -> RclResult<Self> {
+        let node = Node::new_with_namespace(node_name, node_ns, context)?;
+        let mut state_machine = unsafe { rcl_lifecycle_get_zero_initialized_state_machine() };
+
+        {
+            let node_handle = &mut *node.handle.get_mut();
+            unsafe {
+                let allocator = node.allocator.rcl_allocator();
+                let transition_graph_type_support =
+                    rosidl_typesupport_c__get_service_type_support_handle__lifecycle_msgs__srv__GetAvailableTransitions();
+                rcl_lifecycle_state_machine_init(
+                    &mut state_machine as *mut _,
+                    node_handle as *mut _,
+                    rosidl_typesupport_c__get_message_type_support_handle__lifecycle_msgs__msg__TransitionEvent(),
+                    rosidl_typesupport_c__get_service_type_support_handle__lifecycle_msgs__srv__ChangeState(),
+                    rosidl_typesupport_c__get_service_type_support_handle__lifecycle_msgs__srv__GetState(),
+                    rosidl_typesupport_c__get_service_type_support_handle__lifecycle_msgs__srv__GetAvailableStates(),
+                    transition_graph_type_support,
+                    transition_graph_type_support,
+                    true,
+                    &allocator as *const _,
+                )
+                .ok()?;
+            }
+        }
+
+        Ok(Self {
+            node,
+            state_machine: RefCell::new(state_machine),
+            callbacks: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// Returns the state the node is currently in
+    pub fn current_state(&self) -> LifecycleState {
+        let state_machine = &*self.state_machine.borrow();
+        unsafe { (*state_machine.current_state).id }.into()
+    }
+
+    /// Returns the label of the state the node is currently in, e.g. `"unconfigured"`
+    pub fn current_state_label(&self) -> String {
+        let state_machine = &*self.state_machine.borrow();
+        unsafe { CStr::from_ptr((*state_machine.current_state).label) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Registers the callback run while transitioning from unconfigured to inactive
+    pub fn on_configure<F>(&self, callback: F)
+    where
+        F: FnMut() -> TransitionCallbackReturn + 'static,
+    {
+        self.set_callback(LifecycleState::Configuring, callback);
+    }
+
+    /// Registers the callback run while transitioning from inactive to unconfigured
+    pub fn on_cleanup<F>(&self, callback: F)
+    where
+        F: FnMut() -> TransitionCallbackReturn + 'static,
+    {
+        self.set_callback(LifecycleState::CleaningUp, callback);
+    }
+
+    /// Registers the callback run while transitioning from inactive to active
+    pub fn on_activate<F>(&self, callback: F)
+    where
+        F: FnMut() -> TransitionCallbackReturn + 'static,
+    {
+        self.set_callback(LifecycleState::Activating, callback);
+    }
+
+    /// Registers the callback run while transitioning from active to inactive
+    pub fn on_deactivate<F>(&self, callback: F)
+    where
+        F: FnMut() -> TransitionCallbackReturn + 'static,
+    {
+        self.set_callback(LifecycleState::Deactivating, callback);
+    }
+
+    /// Registers the callback run while transitioning from any primary state to finalized
+    pub fn on_shutdown<F>(&self, callback: F)
+    where
+        F: FnMut() -> TransitionCallbackReturn + 'static,
+    {
+        self.set_callback(LifecycleState::ShuttingDown, callback);
+    }
+
+    /// Registers the callback run when another transition callback returned an error
+    ///
+    /// Returning [`TransitionCallbackReturn::Success`] recovers to unconfigured, anything
+    /// else finalizes the node.
+    pub fn on_error<F>(&self, callback: F)
+    where
+        F: FnMut() -> TransitionCallbackReturn + 'static,
+    {
+        self.set_callback(LifecycleState::ErrorProcessing, callback);
+    }
+
+    pub fn configure(&self) -> RclResult<LifecycleState> {
+        self.trigger_transition(LifecycleTransition::Configure)
+    }
+
+    pub fn cleanup(&self) -> RclResult<LifecycleState> {
+        self.trigger_transition(LifecycleTransition::Cleanup)
+    }
+
+    pub fn activate(&self) -> RclResult<LifecycleState> {
+        self.trigger_transition(LifecycleTransition::Activate)
+    }
+
+    pub fn deactivate(&self) -> RclResult<LifecycleState> {
+        self.trigger_transition(LifecycleTransition::Deactivate)
+    }
+
+    /// Finalizes the node from whichever primary state it is in
+    pub fn shutdown(&self) -> RclResult<LifecycleState> {
+        self.trigger_transition(LifecycleTransition::shutdown_from(self.current_state()))
+    }
+
+    /// Drives the state machine through `transition`, running the matching callback
+    ///
+    /// Returns the primary state the node ended up in.
+    pub fn trigger_transition(&self, transition: LifecycleTransition) -> RclResult<LifecycleState> {
+        self.trigger_transition_by_id(transition as u8)?;
+
+        let callback_return = self.execute_callback(self.current_state());
+        self.trigger_transition_by_label(callback_return.label())?;
+
+        if callback_return == TransitionCallbackReturn::Error {
+            let error_return = self.execute_callback(self.current_state());
+            self.trigger_transition_by_label(error_return.label())?;
+        }
+
+        Ok(self.current_state())
+    }
+
+    fn set_callback<F>(&self, state: LifecycleState, callback: F)
+    where
+        F: FnMut() -> TransitionCallbackReturn + 'static,
+    {
+        self.callbacks
+            .borrow_mut()
+            .insert(state, Box::new(callback));
+    }
+
+    fn execute_callback(&self, state: LifecycleState) -> TransitionCallbackReturn {
+        match self.callbacks.borrow_mut().get_mut(&state) {
+            Some(callback) => callback(),
+            None if state == LifecycleState::ErrorProcessing => TransitionCallbackReturn::Failure,
+            None => TransitionCallbackReturn::Success,
+        }
+    }
+
+    fn trigger_transition_by_id(&self, id: u8) -> RclResult {
+        let state_machine = &mut *self.state_machine.borrow_mut();
+        unsafe { rcl_lifecycle_trigger_transition_by_id(state_machine as *mut _, id, true).ok() }
+    }
+
+    fn trigger_transition_by_label(&self, label: *const c_char) -> RclResult {
+        let state_machine = &mut *self.state_machine.borrow_mut();
+        unsafe {
+            rcl_lifecycle_trigger_transition_by_label(state_machine as *mut _, label, true).ok()
+        }
+    }
+}
+
+impl Deref for LifecycleNode {
+    type Target = Node;
+
+    fn deref(&self) -> &Node {
+        &self.node
+    }
+}
+
+impl DerefMut for LifecycleNode {
+    fn deref_mut(&mut self) -> &mut Node {
+        &mut self.node
+    }
+}
+
+impl Drop for LifecycleNode {
+    fn drop(&mut self) {
+        let state_machine = &mut *self.state_machine.borrow_mut();
+        let node_handle = &mut *self.node.handle.get_mut();
+        unsafe {
+            let allocator = self.node.allocator.rcl_allocator();
+            rcl_lifecycle_state_machine_fini(
+                state_machine as *mut _,
+                node_handle as *mut _,
+                &allocator as *const _,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn lifecycle_state_from_id() {
+        let states = [
+            LifecycleState::Unknown,
+            LifecycleState::Unconfigured,
+            LifecycleState::Inactive,
+            LifecycleState::Active,
+            LifecycleState::Finalized,
+            LifecycleState::Configuring,
+            LifecycleState::CleaningUp,
+            LifecycleState::ShuttingDown,
+            LifecycleState::Activating,
+            LifecycleState::Deactivating,
+            LifecycleState::ErrorProcessing,
+        ];
+        for &state in &states {
+            assert_eq!(LifecycleState::from(state as u32), state);
+        }
+        assert_eq!(LifecycleState::from(5), LifecycleState::Unknown);
+        assert_eq!(LifecycleState::from(99), LifecycleState::Unknown);
+    }
+
+    #[test]
+    fn shutdown_transition_leaves_state() {
+        let cases = [
+            (
+                LifecycleState::Unconfigured,
+                LifecycleTransition::UnconfiguredShutdown,
+            ),
+            (
+                LifecycleState::Inactive,
+                LifecycleTransition::InactiveShutdown,
+            ),
+            (LifecycleState::Active, LifecycleTransition::ActiveShutdown),
+        ];
+        for &(state, transition) in &cases {
+            assert_eq!(LifecycleTransition::shutdown_from(state), transition);
+        }
+    }
+
+    #[test]
+    fn configure_activate_deactivate() -> RclResult {
+        let context = Context::default();
+        let node = LifecycleNode::new("lifecycle_test", &context)?;
+        let transitions = Rc::new(Cell::new(0));
+        let count_transition = || {
+            let transitions = Rc::clone(&transitions);
+            move || {
+                transitions.set(transitions.get() + 1);
+                TransitionCallbackReturn::Success
+            }
+        };
+        node.on_configure(count_transition());
+        node.on_activate(count_transition());
+        node.on_deactivate(count_transition());
+        assert_eq!(node.current_state(), LifecycleState::Unconfigured);
+
+        assert_eq!(node.configure()?, LifecycleState::Inactive);
+        assert_eq!(node.activate()?, LifecycleState::Active);
+        assert_eq!(node.deactivate()?, LifecycleState::Inactive);
+        assert_eq!(transitions.get(), 3);
+
+        assert_eq!(node.shutdown()?, LifecycleState::Finalized);
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use super::{Node, PublisherHandle, SubscriptionHandle};
+use std::rc::{Rc, Weak};
+
+/// A publisher or subscription that was created through a node, see
+/// [`Node::local_publishers`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalTopicEntity {
+    /// Fully qualified name of the topic, after remapping
+    pub topic_name: String,
+    /// Name of the message type, e.g. `"std_msgs/msg/String"`
+    pub type_name: String,
+}
+
+/// The publishers and subscriptions a node created, as weak references, so the bookkeeping
+/// doesn't keep any of them alive
+#[derive(Default)]
+pub(crate) struct LocalEntities {
+    publishers: Vec<(Weak<PublisherHandle>, String)>,
+    subscriptions: Vec<(Weak<SubscriptionHandle>, String)>,
+}
+
+impl Node {
+    pub(crate) fn register_local_publisher(&self, handle: &Rc<PublisherHandle>, type_name: String) {
+        let mut local_entities = self.local_entities.borrow_mut();
+        local_entities
+            .publishers
+            .retain(|(handle, _)| handle.strong_count() > 0);
+        local_entities
+            .publishers
+            .push((Rc::downgrade(handle), type_name));
+    }
+
+    pub(crate) fn register_local_subscription(
+        &self,
+        handle: &Rc<SubscriptionHandle>,
+        type_name: String,
+    ) {
+        let mut local_entities = self.local_entities.borrow_mut();
+        local_entities
+            .subscriptions
+            .retain(|(handle, _)| handle.strong_count() > 0);
+        local_entities
+            .subscriptions
+            .push((Rc::downgrade(handle), type_name));
+    }
+
+    /// Returns the publishers created through this node that still exist, in the order they
+    /// were created
+    ///
+    /// Unlike [`Node::get_publisher_names_and_types_by_node`], this only looks at the node's
+    /// own bookkeeping and doesn't depend on discovery.
+    pub fn local_publishers(&self) -> Vec<LocalTopicEntity> {
+        self.local_entities
+            .borrow()
+            .publishers
+            .iter()
+            .filter_map(|(handle, type_name)| {
+                handle.upgrade().map(|handle| LocalTopicEntity {
+                    topic_name: handle.topic_name(),
+                    type_name: type_name.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the subscriptions created through this node that still exist, in the order they
+    /// were created
+    ///
+    /// This includes the subscription to `/clock` while `use_sim_time` is enabled.
+    pub fn local_subscriptions(&self) -> Vec<LocalTopicEntity> {
+        self.local_entities
+            .borrow()
+            .subscriptions
+            .iter()
+            .filter_map(|(handle, type_name)| {
+                handle.upgrade().map(|handle| LocalTopicEntity {
+                    topic_name: handle.topic_name(),
+                    type_name: type_name.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the number of timers created through this node that still exist
+    pub fn local_timer_count(&self) -> usize {
+        self.timers
+            .borrow()
+            .iter()
+            .filter(|timer| timer.strong_count() > 0)
+            .count()
+    }
+}
@@ -1,47 +1,110 @@
-use crate::error::{RclResult, ToRclResult};
+use self::local::LocalEntities;
+use self::parameter::load_parameter_overrides;
+use crate::error::{record_error_state, RclError, RclResult, ToRclResult};
 use crate::qos::QoSProfile;
-use crate::{Context, ContextHandle, Handle};
+use crate::{Allocator, Clock, ClockType, Context, ContextHandle, Handle, Time};
 use rcl_sys::*;
 use std::cell::{Ref, RefCell, RefMut};
-use std::ffi::CString;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
 use std::rc::{Rc, Weak};
 
+pub mod builder;
+pub use self::builder::*;
+pub mod dynamic;
+pub use self::dynamic::*;
+pub mod event;
+pub use self::event::*;
+pub mod graph;
+pub mod guard_condition;
+pub use self::guard_condition::*;
+pub mod lifecycle;
+pub use self::lifecycle::*;
+pub mod local;
+pub use self::local::*;
+pub mod parameter;
+pub use self::parameter::*;
 pub mod publisher;
 pub use self::publisher::*;
+mod sim_time;
+pub mod sub_node;
+pub use self::sub_node::*;
 pub mod subscription;
 pub use self::subscription::*;
+pub mod timer;
+pub use self::timer::*;
 
-pub struct NodeHandle(RefCell<rcl_node_t>);
+pub struct NodeHandle {
+    pub(crate) handle: RefCell<rcl_node_t>,
+    /// Whether the node is finalized on drop, which isn't the case for [`Node::from_raw`]
+    pub(crate) owned: bool,
+    // Publishers and subscriptions only keep the node handle, but finalizing the node still
+    // needs the middleware of its context. Dropped after the node is finalized.
+    pub(crate) _context: Rc<ContextHandle>,
+}
 
 impl<'a> Handle<rcl_node_t> for &'a NodeHandle {
     type DerefT = Ref<'a, rcl_node_t>;
     type DerefMutT = RefMut<'a, rcl_node_t>;
 
     fn get(self) -> Self::DerefT {
-        self.0.borrow()
+        self.handle.borrow()
     }
 
     fn get_mut(self) -> Self::DerefMutT {
-        self.0.borrow_mut()
+        self.handle.borrow_mut()
     }
 }
 
 impl Drop for NodeHandle {
     fn drop(&mut self) {
+        if !self.owned {
+            return;
+        }
         let handle = &mut *self.get_mut();
         unsafe {
-            rcl_node_fini(handle as *mut _).unwrap();
+            rcl_node_fini(handle as *mut _).unwrap_in_drop("rcl_node_fini");
         }
     }
 }
 
+/// A ROS node, from which publishers, subscriptions, timers etc. are created
+///
+/// All of them can be created through `&self`, so a node can be shared as an `Rc<Node>`, e.g.
+/// between a struct and callbacks that create more entities. It is bound to the thread it was
+/// created on, hence `Rc` rather than `Arc`. Publishers and subscriptions keep the underlying
+/// rcl node alive, so it is finalized once, after the last of them is dropped.
+///
+/// ```ignore
+/// let node = Rc::new(context.create_node("my_node")?);
+/// let timer_node = Rc::clone(&node);
+/// let _timer = node.create_timer(Duration::from_secs(1), move || {
+///     println!("{}", timer_node.fully_qualified_name());
+/// })?;
+/// spin(&node)?;
+/// ```
 pub struct Node {
-    handle: Rc<NodeHandle>,
+    pub(crate) handle: Rc<NodeHandle>,
     pub(crate) context: Rc<ContextHandle>,
-    pub(crate) subscriptions: Vec<Weak<dyn SubscriptionBase>>,
+    pub(crate) allocator: Rc<dyn Allocator>,
+    pub(crate) clock: Rc<Clock>,
+    sim_time_subscription: RefCell<Option<Rc<dyn SubscriptionBase>>>,
+    // Behind a RefCell, so entities can be created through a shared `Rc<Node>`
+    pub(crate) subscriptions: RefCell<Vec<Weak<dyn SubscriptionBase>>>,
+    pub(crate) events: RefCell<Vec<Weak<QoSEvent>>>,
+    pub(crate) timers: RefCell<Vec<Weak<Timer>>>,
+    pub(crate) guard_conditions: RefCell<Vec<Weak<GuardCondition>>>,
+    local_entities: RefCell<LocalEntities>,
+    pub(crate) parameters: RefCell<HashMap<String, DeclaredParameter>>,
+    parameter_overrides: HashMap<String, ParameterValue>,
+    pub(crate) parameter_callbacks: RefCell<ParameterCallbacks>,
 }
 
 impl Node {
+    /// Creates a node with the default options, see [`Node::builder`] for the others
+    ///
+    /// Invalid names are reported as [`RclError::NodeInvalidName`].
     #[allow(clippy::new_ret_no_self)]
     pub fn new(node_name: &str, context: &Context) -> RclResult<Node> {
         Self::new_with_namespace(node_name, "", context)
@@ -52,33 +115,181 @@ impl Node {
         node_ns: &str,
         context: &Context,
     ) -> RclResult<Node> {
-        let raw_node_name = CString::new(node_name).unwrap();
-        let raw_node_ns = CString::new(node_ns).unwrap();
+        NodeBuilder::new(node_name, context)
+            .namespace(node_ns)
+            .build()
+    }
 
-        let mut node_handle = unsafe { rcl_get_zero_initialized_node() };
-        let context_handle = &mut *context.handle.get_mut();
+    /// Returns a builder for configuring the node's options before creating it
+    pub fn builder<'a>(node_name: &str, context: &'a Context) -> NodeBuilder<'a> {
+        NodeBuilder::new(node_name, context)
+    }
 
-        unsafe {
-            let node_options = rcl_node_get_default_options();
-            rcl_node_init(
-                &mut node_handle as *mut _,
-                raw_node_name.as_ptr(),
-                raw_node_ns.as_ptr(),
-                context_handle as *mut _,
-                &node_options as *const _,
-            )
-            .ok()?;
+    /// Wraps a node that was created outside of rclrs, e.g. by C code, without taking ownership
+    ///
+    /// The node is not finalized when the wrapper is dropped, that stays up to whoever created
+    /// it.
+    ///
+    /// # Safety
+    ///
+    /// `node` has to point to an initialized node that belongs to `context` and stays
+    /// initialized for as long as the returned node, and any publisher or subscription created
+    /// from it, exists.
+    pub unsafe fn from_raw(node: *const rcl_node_t, context: &Context) -> RclResult<Node> {
+        if !rcl_node_is_valid(node) {
+            return Err(RclError::NodeInvalid);
         }
+        // An rcl node only points to its state, so a copy refers to the same node
+        let handle = Rc::new(NodeHandle {
+            handle: RefCell::new(std::ptr::read(node)),
+            owned: false,
+            _context: context.handle.clone(),
+        });
+        Self::from_handle(handle, context, context.allocator.clone(), HashMap::new())
+    }
 
-        let handle = Rc::new(NodeHandle(RefCell::new(node_handle)));
+    /// Sets up everything around an initialized node, like its clock and parameters
+    pub(crate) fn from_handle(
+        handle: Rc<NodeHandle>,
+        context: &Context,
+        allocator: Rc<dyn Allocator>,
+        parameter_overrides: HashMap<String, ParameterValue>,
+    ) -> RclResult<Node> {
+        let clock = Rc::new(Clock::new(ClockType::RosTime, allocator.clone())?);
 
-        Ok(Node {
+        let mut node = Node {
             handle,
             context: context.handle.clone(),
-            subscriptions: vec![],
+            allocator,
+            clock,
+            sim_time_subscription: RefCell::new(None),
+            subscriptions: RefCell::new(vec![]),
+            events: RefCell::new(vec![]),
+            timers: RefCell::new(vec![]),
+            guard_conditions: RefCell::new(vec![]),
+            local_entities: RefCell::new(LocalEntities::default()),
+            parameters: RefCell::new(HashMap::new()),
+            parameter_overrides: HashMap::new(),
+            parameter_callbacks: RefCell::new(Default::default()),
+        };
+        node.parameter_overrides = load_parameter_overrides(&node, parameter_overrides)?;
+        Ok(node)
+    }
+
+    /// Fails with [`RclError::NodeInvalid`] once the node can't be used anymore, e.g. after its
+    /// context was shut down
+    ///
+    /// Checked before creating entities, which rcl would refuse with less specific errors.
+    pub fn ensure_valid(&self) -> RclResult {
+        let node_handle = &*self.handle.get();
+        if unsafe { rcl_node_is_valid(node_handle as *const _) } {
+            Ok(())
+        } else {
+            // rcl_node_is_valid sets an error message as well
+            record_error_state();
+            Err(RclError::NodeInvalid)
+        }
+    }
+
+    /// Returns the underlying rcl node, which stays owned by this node
+    ///
+    /// The pointer is only valid for as long as the node exists. Don't finalize it.
+    pub fn as_raw(&self) -> *const rcl_node_t {
+        self.handle.handle.as_ptr()
+    }
+
+    /// Like [`Node::as_raw`], for rcl functions that take a mutable node
+    pub fn as_raw_mut(&self) -> *mut rcl_node_t {
+        self.handle.handle.as_ptr()
+    }
+
+    /// Returns the underlying rmw node, for interop with middleware-specific APIs
+    ///
+    /// The pointer is owned by the node and is only valid for as long as it exists.
+    pub fn rmw_node_handle(&self) -> *mut rmw_node_t {
+        let node_handle = &*self.handle.get();
+        unsafe { rcl_node_get_rmw_handle(node_handle as *const _) }
+    }
+
+    pub fn name(&self) -> String {
+        let node_handle = &*self.handle.get();
+        unsafe { c_str_to_string(rcl_node_get_name(node_handle as *const _)) }
+    }
+
+    /// Returns the namespace the node ended up in, which a `-r __ns:=...` argument takes
+    /// precedence over the one it was built with
+    pub fn namespace(&self) -> String {
+        let node_handle = &*self.handle.get();
+        unsafe { c_str_to_string(rcl_node_get_namespace(node_handle as *const _)) }
+    }
+
+    /// Returns the namespace and name of the node, e.g. `"/my_ns/my_node"`
+    pub fn fully_qualified_name(&self) -> String {
+        let node_handle = &*self.handle.get();
+        unsafe { c_str_to_string(rcl_node_get_fully_qualified_name(node_handle as *const _)) }
+    }
+
+    /// Reads back the options the node was created with
+    pub fn options(&self) -> RclResult<NodeOptions> {
+        let node_handle = &*self.handle.get();
+        let mut domain_id = 0;
+
+        // The options are owned by the node, so they are only copied out here
+        let options = unsafe { rcl_node_get_options(node_handle as *const _).as_ref() }
+            .ok_or(RclError::NodeInvalid)?;
+        unsafe {
+            rcl_node_get_domain_id(node_handle as *const _, &mut domain_id as *mut _).ok()?;
+        }
+
+        Ok(NodeOptions {
+            use_global_arguments: options.use_global_arguments,
+            enable_rosout: options.enable_rosout,
+            domain_id,
         })
     }
 
+    /// Returns the fully qualified name `topic` resolves to, once expanded against the node's
+    /// namespace and remapped
+    ///
+    /// This is the name a publisher or subscription created with `topic` would use.
+    pub fn resolve_topic_name(&self, topic: &str) -> RclResult<String> {
+        let node_handle = &*self.handle.get();
+        let topic_c_string = CString::new(topic).map_err(|_| RclError::TopicNameInvalid)?;
+        let mut resolved_name: *mut c_char = std::ptr::null_mut();
+
+        unsafe {
+            rcl_node_resolve_name(
+                node_handle as *const _,
+                topic_c_string.as_ptr(),
+                self.allocator.rcl_allocator(),
+                false,
+                false,
+                &mut resolved_name as *mut _,
+            )
+            .ok()?;
+        }
+
+        // The name was allocated with the allocator passed in
+        let name = unsafe { CStr::from_ptr(resolved_name) }
+            .to_string_lossy()
+            .into_owned();
+        let rcl_allocator = self.allocator.rcl_allocator();
+        unsafe {
+            rcl_allocator.deallocate.unwrap()(resolved_name as *mut c_void, rcl_allocator.state);
+        }
+        Ok(name)
+    }
+
+    /// Returns the current time of the node's ROS clock
+    pub fn now(&self) -> RclResult<Time> {
+        self.clock.now()
+    }
+
+    /// Returns the node's ROS clock, which follows simulated time when that is enabled
+    pub fn get_clock(&self) -> Rc<Clock> {
+        self.clock.clone()
+    }
+
     // TODO: make publisher's lifetime depend on node's lifetime
     pub fn create_publisher<T>(&self, topic: &str, qos: QoSProfile) -> RclResult<Publisher<T>>
     where
@@ -87,20 +298,109 @@ impl Node {
         Publisher::<T>::new(self, topic, qos)
     }
 
+    /// Creates a publisher for a message type that is only known at runtime
+    pub fn create_dynamic_publisher(
+        &self,
+        topic: &str,
+        type_name: &str,
+        qos: QoSProfile,
+    ) -> RclResult<DynamicPublisher> {
+        DynamicPublisher::new(self, topic, type_name, qos)
+    }
+
+    /// Creates a poll-only subscription for a message type that is only known at runtime
+    pub fn create_dynamic_subscription(
+        &self,
+        topic: &str,
+        type_name: &str,
+        qos: QoSProfile,
+    ) -> RclResult<DynamicSubscription> {
+        DynamicSubscription::new(self, topic, type_name, qos)
+    }
+
     // TODO: make subscription's lifetime depend on node's lifetime
     pub fn create_subscription<T, F>(
-        &mut self,
+        &self,
+        topic: &str,
+        qos: QoSProfile,
+        callback: F,
+    ) -> RclResult<Rc<Subscription<T>>>
+    where
+        T: rclrs_common::traits::MessageDefinition<T> + Default,
+        F: FnMut(&T) + Sized + 'static,
+    {
+        self.create_subscription_with_options(topic, qos, SubscriptionOptions::default(), callback)
+    }
+
+    pub fn create_subscription_with_options<T, F>(
+        &self,
         topic: &str,
         qos: QoSProfile,
+        options: SubscriptionOptions,
         callback: F,
     ) -> RclResult<Rc<Subscription<T>>>
     where
         T: rclrs_common::traits::MessageDefinition<T> + Default,
         F: FnMut(&T) + Sized + 'static,
     {
-        let subscription = Rc::new(Subscription::<T>::new(self, topic, qos, callback)?);
+        let subscription = Rc::new(Subscription::<T>::new_with_options(
+            self, topic, qos, options, callback,
+        )?);
         self.subscriptions
+            .borrow_mut()
             .push(Rc::downgrade(&subscription) as Weak<dyn SubscriptionBase>);
         Ok(subscription)
     }
+
+    /// Creates a subscription whose callback also gets the [`MessageInfo`] of each message,
+    /// e.g. for its source timestamp
+    pub fn create_subscription_with_info<T, F>(
+        &self,
+        topic: &str,
+        qos: QoSProfile,
+        callback: F,
+    ) -> RclResult<Rc<Subscription<T>>>
+    where
+        T: rclrs_common::traits::MessageDefinition<T> + Default,
+        F: FnMut(&T, &MessageInfo) + Sized + 'static,
+    {
+        let subscription = Rc::new(Subscription::<T>::new_with_info(
+            self,
+            topic,
+            qos,
+            SubscriptionOptions::default(),
+            callback,
+        )?);
+        self.subscriptions
+            .borrow_mut()
+            .push(Rc::downgrade(&subscription) as Weak<dyn SubscriptionBase>);
+        Ok(subscription)
+    }
+
+    /// Creates a subscription without a callback, for reading messages on demand
+    ///
+    /// Spinning the node leaves the subscription alone, so its messages queue up until
+    /// [`Subscription::take`], [`Subscription::take_all`] or another take method is called, and
+    /// the oldest ones are dropped once the QoS depth is exceeded. A callback set later with
+    /// [`Subscription::set_callback`] is never called.
+    pub fn create_polling_subscription<T>(
+        &self,
+        topic: &str,
+        qos: QoSProfile,
+    ) -> RclResult<Rc<Subscription<T>>>
+    where
+        T: rclrs_common::traits::MessageDefinition<T> + Default,
+    {
+        // Not added to the node's subscriptions, which are what spin takes messages from
+        Ok(Rc::new(Subscription::<T>::new(self, topic, qos, |_| {})?))
+    }
+}
+
+/// rcl returns `NULL` for the names of an invalid node or entity, which is reported as an
+/// empty name
+pub(crate) unsafe fn c_str_to_string(string: *const c_char) -> String {
+    if string.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(string).to_string_lossy().into_owned()
 }
@@ -0,0 +1,853 @@
+use crate::error::{record_error_state, RclError, RclResult, ToRclResult};
+use crate::{Allocator, Handle, Node};
+use failure::Fail;
+use rcl_sys::*;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_void};
+use std::rc::Rc;
+
+/// Value of a node parameter
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParameterValue {
+    Bool(bool),
+    Integer(i64),
+    Double(f64),
+    String(String),
+    ByteArray(Vec<u8>),
+    BoolArray(Vec<bool>),
+    IntegerArray(Vec<i64>),
+    DoubleArray(Vec<f64>),
+    StringArray(Vec<String>),
+}
+
+/// Type of a [`ParameterValue`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParameterType {
+    Bool,
+    Integer,
+    Double,
+    String,
+    ByteArray,
+    BoolArray,
+    IntegerArray,
+    DoubleArray,
+    StringArray,
+}
+
+/// Inclusive range an integer parameter has to lie in
+///
+/// A `step` of zero allows any value in the range, otherwise only `from_value` plus a multiple
+/// of `step` is allowed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntegerRange {
+    pub from_value: i64,
+    pub to_value: i64,
+    pub step: u64,
+}
+
+/// Inclusive range a floating point parameter has to lie in, see [`IntegerRange`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloatingPointRange {
+    pub from_value: f64,
+    pub to_value: f64,
+    pub step: f64,
+}
+
+/// Metadata of a declared parameter, as reported by the `describe_parameters` service
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterDescriptor {
+    pub name: String,
+    /// Filled in from the default value when the parameter is declared
+    pub parameter_type: ParameterType,
+    pub description: String,
+    /// Read-only parameters keep the value they were declared with
+    pub read_only: bool,
+    pub integer_range: Option<IntegerRange>,
+    pub floating_point_range: Option<FloatingPointRange>,
+}
+
+impl IntegerRange {
+    fn contains(&self, value: i64) -> bool {
+        let offset = value.wrapping_sub(self.from_value) as u64;
+        value >= self.from_value
+            && value <= self.to_value
+            // A step of zero has no remainder, so any value passes
+            && offset.checked_rem(self.step).unwrap_or(0) == 0
+    }
+}
+
+impl FloatingPointRange {
+    fn contains(&self, value: f64) -> bool {
+        let steps = (value - self.from_value) / self.step;
+        value >= self.from_value
+            && value <= self.to_value
+            && (self.step == 0.0 || (steps - steps.round()).abs() < 1e-9)
+    }
+}
+
+impl ParameterDescriptor {
+    /// Checks `value` against the type and ranges of the descriptor
+    fn check(&self, value: &ParameterValue) -> Result<(), ParameterError> {
+        if value.parameter_type() != self.parameter_type {
+            return Err(ParameterError::TypeMismatch {
+                name: self.name.clone(),
+                expected: self.parameter_type,
+                actual: value.parameter_type(),
+            });
+        }
+
+        let in_range = match (value, &self.integer_range, &self.floating_point_range) {
+            (ParameterValue::Integer(value), Some(range), _) => range.contains(*value),
+            (ParameterValue::Double(value), _, Some(range)) => range.contains(*value),
+            _ => true,
+        };
+        if in_range {
+            Ok(())
+        } else {
+            Err(ParameterError::OutOfRange(self.name.clone()))
+        }
+    }
+}
+
+/// A parameter name together with a value for it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub value: ParameterValue,
+}
+
+/// Verdict of an on-set-parameters callback on a proposed change
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetParametersResult {
+    pub successful: bool,
+    /// Why the change was rejected, reported back to whoever tried to set the parameters
+    pub reason: String,
+}
+
+pub type OnSetParametersCallback = dyn Fn(&[Parameter]) -> SetParametersResult;
+
+/// Identifies a callback registered with [`Node::add_on_set_parameters_callback`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OnSetParametersCallbackHandle(usize);
+
+#[derive(Default)]
+pub(crate) struct ParameterCallbacks {
+    next_id: usize,
+    callbacks: Vec<(usize, Rc<OnSetParametersCallback>)>,
+}
+
+pub(crate) struct DeclaredParameter {
+    value: ParameterValue,
+    descriptor: ParameterDescriptor,
+}
+
+impl ParameterValue {
+    pub fn parameter_type(&self) -> ParameterType {
+        match self {
+            ParameterValue::Bool(_) => ParameterType::Bool,
+            ParameterValue::Integer(_) => ParameterType::Integer,
+            ParameterValue::Double(_) => ParameterType::Double,
+            ParameterValue::String(_) => ParameterType::String,
+            ParameterValue::ByteArray(_) => ParameterType::ByteArray,
+            ParameterValue::BoolArray(_) => ParameterType::BoolArray,
+            ParameterValue::IntegerArray(_) => ParameterType::IntegerArray,
+            ParameterValue::DoubleArray(_) => ParameterType::DoubleArray,
+            ParameterValue::StringArray(_) => ParameterType::StringArray,
+        }
+    }
+
+    /// Copies the value out of a variant filled in by rcl's YAML parser
+    ///
+    /// Returns `None` for a variant without a value.
+    unsafe fn from_rcl_variant(variant: &rcl_variant_t) -> Option<Self> {
+        let value = if let Some(value) = variant.bool_value.as_ref() {
+            ParameterValue::Bool(*value)
+        } else if let Some(value) = variant.integer_value.as_ref() {
+            ParameterValue::Integer(*value)
+        } else if let Some(value) = variant.double_value.as_ref() {
+            ParameterValue::Double(*value)
+        } else if !variant.string_value.is_null() {
+            ParameterValue::String(c_str_to_string(variant.string_value))
+        } else if let Some(array) = variant.byte_array_value.as_ref() {
+            ParameterValue::ByteArray(slice_to_vec(array.values, array.size))
+        } else if let Some(array) = variant.bool_array_value.as_ref() {
+            ParameterValue::BoolArray(slice_to_vec(array.values, array.size))
+        } else if let Some(array) = variant.integer_array_value.as_ref() {
+            ParameterValue::IntegerArray(slice_to_vec(array.values, array.size))
+        } else if let Some(array) = variant.double_array_value.as_ref() {
+            ParameterValue::DoubleArray(slice_to_vec(array.values, array.size))
+        } else if let Some(array) = variant.string_array_value.as_ref() {
+            ParameterValue::StringArray(
+                slice_to_vec(array.data, array.size)
+                    .into_iter()
+                    .map(|string| c_str_to_string(string))
+                    .collect(),
+            )
+        } else {
+            return None;
+        };
+        Some(value)
+    }
+}
+
+/// Rust type of a parameter, for declaring it as a [`TypedParameter`]
+pub trait ParameterVariant: Sized {
+    fn into_parameter_value(self) -> ParameterValue;
+    /// Returns `None` if `value` holds another type
+    fn from_parameter_value(value: ParameterValue) -> Option<Self>;
+}
+
+macro_rules! impl_parameter_variant {
+    ($type:ty, $variant:ident) => {
+        impl ParameterVariant for $type {
+            fn into_parameter_value(self) -> ParameterValue {
+                ParameterValue::$variant(self)
+            }
+
+            fn from_parameter_value(value: ParameterValue) -> Option<Self> {
+                match value {
+                    ParameterValue::$variant(value) => Some(value),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+impl_parameter_variant!(bool, Bool);
+impl_parameter_variant!(i64, Integer);
+impl_parameter_variant!(f64, Double);
+impl_parameter_variant!(String, String);
+impl_parameter_variant!(Vec<u8>, ByteArray);
+impl_parameter_variant!(Vec<bool>, BoolArray);
+impl_parameter_variant!(Vec<i64>, IntegerArray);
+impl_parameter_variant!(Vec<f64>, DoubleArray);
+impl_parameter_variant!(Vec<String>, StringArray);
+
+/// Declared parameter whose type is known at compile time, see [`Node::declare_typed_parameter`]
+///
+/// It refers to the node's parameters, so a change through [`Node::set_parameter`] shows up in
+/// [`TypedParameter::get`], and a set through either goes through the same validation and
+/// on-set-parameters callbacks.
+pub struct TypedParameter<'node, T: ParameterVariant> {
+    node: &'node Node,
+    name: String,
+    value_type: PhantomData<T>,
+}
+
+impl<'node, T: ParameterVariant> TypedParameter<'node, T> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the current value, or `None` once the parameter was undeclared
+    pub fn get(&self) -> Option<T> {
+        // The type is checked whenever the value changes, so this never holds another type
+        self.node
+            .get_parameter(&self.name)
+            .and_then(T::from_parameter_value)
+    }
+
+    pub fn set(&self, value: T) -> Result<(), ParameterError> {
+        self.node
+            .set_parameter(&self.name, value.into_parameter_value())
+    }
+}
+
+unsafe fn c_str_to_string(string: *const c_char) -> String {
+    CStr::from_ptr(string).to_string_lossy().into_owned()
+}
+
+unsafe fn slice_to_vec<T: Clone>(values: *const T, size: usize) -> Vec<T> {
+    if size == 0 {
+        return vec![];
+    }
+    std::slice::from_raw_parts(values, size).to_vec()
+}
+
+/// Error returned when declaring or setting a parameter fails
+#[derive(Debug, Fail, PartialEq)]
+pub enum ParameterError {
+    #[fail(display = "parameter `{}` has already been declared", _0)]
+    AlreadyDeclared(String),
+    #[fail(display = "parameter `{}` has not been declared", _0)]
+    NotDeclared(String),
+    #[fail(display = "parameter `{}` is read-only", _0)]
+    ReadOnly(String),
+    #[fail(
+        display = "parameter `{}` is of type {:?}, not {:?}",
+        name, expected, actual
+    )]
+    TypeMismatch {
+        name: String,
+        expected: ParameterType,
+        actual: ParameterType,
+    },
+    #[fail(display = "value of parameter `{}` is out of range", _0)]
+    OutOfRange(String),
+    #[fail(display = "parameter change was rejected: {}", _0)]
+    Rejected(String),
+}
+
+impl Node {
+    /// Declares a parameter, returning the value it starts out with
+    ///
+    /// That is the value from a `--params-file` for this node if there is one, and
+    /// `default_value` otherwise.
+    pub fn declare_parameter(
+        &self,
+        name: &str,
+        default_value: ParameterValue,
+    ) -> Result<ParameterValue, ParameterError> {
+        let descriptor = ParameterDescriptor {
+            name: name.to_owned(),
+            parameter_type: default_value.parameter_type(),
+            description: String::new(),
+            read_only: false,
+            integer_range: None,
+            floating_point_range: None,
+        };
+        self.declare_parameter_with_descriptor(name, default_value, descriptor)
+    }
+
+    /// Like [`Node::declare_parameter`], returning a handle that gets and sets values of type `T`
+    ///
+    /// ```ignore
+    /// let rate = node.declare_typed_parameter("rate", 10_i64)?;
+    /// rate.set(20)?;
+    /// ```
+    pub fn declare_typed_parameter<T: ParameterVariant>(
+        &self,
+        name: &str,
+        default_value: T,
+    ) -> Result<TypedParameter<'_, T>, ParameterError> {
+        self.declare_parameter(name, default_value.into_parameter_value())?;
+        Ok(TypedParameter {
+            node: self,
+            name: name.to_owned(),
+            value_type: PhantomData,
+        })
+    }
+
+    /// Declares a parameter with a description and constraints on its value
+    ///
+    /// The name and type in `descriptor` are filled in from `name` and `default_value`.
+    pub fn declare_parameter_with_descriptor(
+        &self,
+        name: &str,
+        default_value: ParameterValue,
+        mut descriptor: ParameterDescriptor,
+    ) -> Result<ParameterValue, ParameterError> {
+        let mut parameters = self.parameters.borrow_mut();
+        if parameters.contains_key(name) {
+            return Err(ParameterError::AlreadyDeclared(name.to_owned()));
+        }
+
+        descriptor.name = name.to_owned();
+        descriptor.parameter_type = default_value.parameter_type();
+        let value = self
+            .parameter_overrides
+            .get(name)
+            .cloned()
+            .unwrap_or(default_value);
+        descriptor.check(&value)?;
+
+        parameters.insert(
+            name.to_owned(),
+            DeclaredParameter {
+                value: value.clone(),
+                descriptor,
+            },
+        );
+        Ok(value)
+    }
+
+    /// Returns the value of a declared parameter, or `None` if it isn't declared
+    pub fn get_parameter(&self, name: &str) -> Option<ParameterValue> {
+        self.parameters
+            .borrow()
+            .get(name)
+            .map(|parameter| parameter.value.clone())
+    }
+
+    /// Returns the parameter values supplied for this node, whether declared yet or not
+    ///
+    /// These come from `--params-file` and `-p name:=value` arguments, and from
+    /// [`crate::NodeBuilder::parameter_override`], merged in the order of precedence they
+    /// have when a parameter is declared.
+    pub fn get_parameter_overrides(&self) -> HashMap<String, ParameterValue> {
+        self.parameter_overrides.clone()
+    }
+
+    pub fn has_parameter(&self, name: &str) -> bool {
+        self.parameters.borrow().contains_key(name)
+    }
+
+    /// Removes a declared parameter, after which it can be declared again
+    ///
+    /// Read-only parameters can't be undeclared.
+    pub fn undeclare_parameter(&self, name: &str) -> Result<(), ParameterError> {
+        let mut declared = self.parameters.borrow_mut();
+        let parameter = declared
+            .get(name)
+            .ok_or_else(|| ParameterError::NotDeclared(name.to_owned()))?;
+        if parameter.descriptor.read_only {
+            return Err(ParameterError::ReadOnly(name.to_owned()));
+        }
+        declared.remove(name);
+        Ok(())
+    }
+
+    pub fn set_parameter(&self, name: &str, value: ParameterValue) -> Result<(), ParameterError> {
+        self.set_parameters(&[Parameter {
+            name: name.to_owned(),
+            value,
+        }])
+    }
+
+    /// Sets several parameters at once
+    ///
+    /// Either all of the parameters are set or, if any of them fails its descriptor or is
+    /// rejected by an on-set-parameters callback, none of them.
+    pub fn set_parameters(&self, parameters: &[Parameter]) -> Result<(), ParameterError> {
+        {
+            let declared = self.parameters.borrow();
+            for parameter in parameters {
+                let descriptor = &declared
+                    .get(&parameter.name)
+                    .ok_or_else(|| ParameterError::NotDeclared(parameter.name.clone()))?
+                    .descriptor;
+                if descriptor.read_only {
+                    return Err(ParameterError::ReadOnly(parameter.name.clone()));
+                }
+                descriptor.check(&parameter.value)?;
+            }
+        }
+
+        // The callbacks may read parameters or (un)register callbacks, so nothing is borrowed
+        // while they run
+        let callbacks: Vec<Rc<OnSetParametersCallback>> = self
+            .parameter_callbacks
+            .borrow()
+            .callbacks
+            .iter()
+            .map(|(_, callback)| Rc::clone(callback))
+            .collect();
+        for callback in callbacks {
+            let result = callback(parameters);
+            if !result.successful {
+                return Err(ParameterError::Rejected(result.reason));
+            }
+        }
+
+        let mut declared = self.parameters.borrow_mut();
+        for parameter in parameters {
+            // Skips parameters that one of the callbacks undeclared in the meantime
+            if let Some(declared_parameter) = declared.get_mut(&parameter.name) {
+                declared_parameter.value = parameter.value.clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Registers `callback` to validate every change of this node's parameters
+    ///
+    /// The callbacks run in order of registration before parameters are set, and the first one
+    /// that rejects the change aborts it.
+    pub fn add_on_set_parameters_callback<F>(&self, callback: F) -> OnSetParametersCallbackHandle
+    where
+        F: Fn(&[Parameter]) -> SetParametersResult + 'static,
+    {
+        let mut parameter_callbacks = self.parameter_callbacks.borrow_mut();
+        let id = parameter_callbacks.next_id;
+        parameter_callbacks.next_id += 1;
+        parameter_callbacks.callbacks.push((id, Rc::new(callback)));
+        OnSetParametersCallbackHandle(id)
+    }
+
+    /// Unregisters a callback, returns `false` if it was already removed
+    pub fn remove_on_set_parameters_callback(&self, handle: OnSetParametersCallbackHandle) -> bool {
+        let callbacks = &mut self.parameter_callbacks.borrow_mut().callbacks;
+        let count = callbacks.len();
+        callbacks.retain(|(id, _)| *id != handle.0);
+        callbacks.len() != count
+    }
+
+    /// Returns the names of all declared parameters, sorted
+    pub fn list_parameters(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.parameters.borrow().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn describe_parameter(&self, name: &str) -> Result<ParameterDescriptor, ParameterError> {
+        self.parameters
+            .borrow()
+            .get(name)
+            .map(|parameter| parameter.descriptor.clone())
+            .ok_or_else(|| ParameterError::NotDeclared(name.to_owned()))
+    }
+}
+
+/// Collects the parameter values for `node` from its arguments, with `builder_overrides` from
+/// [`crate::NodeBuilder::parameter_override`] in between
+///
+/// Later sources take precedence: parameter files, then the builder, then `-p` arguments.
+pub(crate) fn load_parameter_overrides(
+    node: &Node,
+    builder_overrides: HashMap<String, ParameterValue>,
+) -> RclResult<HashMap<String, ParameterValue>> {
+    let node_handle = &*node.handle.get();
+    let (fully_qualified_name, options) = unsafe {
+        let name = rcl_node_get_fully_qualified_name(node_handle as *const _);
+        let options = rcl_node_get_options(node_handle as *const _).as_ref();
+        (c_str_to_string(name), options.ok_or(RclError::NodeInvalid)?)
+    };
+
+    let mut arguments = vec![];
+    if options.use_global_arguments {
+        arguments.push(unsafe { &(*node_handle.context).global_arguments });
+    }
+    arguments.push(&options.arguments);
+
+    let mut overrides = HashMap::new();
+    for arguments in &arguments {
+        for file in param_files(arguments, node.allocator.as_ref())? {
+            parse_yaml_file(
+                &file,
+                &fully_qualified_name,
+                &mut overrides,
+                node.allocator.as_ref(),
+            )?;
+        }
+    }
+    overrides.extend(builder_overrides);
+    for arguments in &arguments {
+        command_line_overrides(arguments, &fully_qualified_name, &mut overrides)?;
+    }
+    Ok(overrides)
+}
+
+/// Adds the values of `-p name:=value` arguments
+pub(crate) fn command_line_overrides(
+    arguments: &rcl_arguments_t,
+    fully_qualified_name: &str,
+    overrides: &mut HashMap<String, ParameterValue>,
+) -> RclResult {
+    let mut params: *mut rcl_params_t = std::ptr::null_mut();
+    unsafe {
+        rcl_arguments_get_param_overrides(arguments as *const _, &mut params as *mut _).ok()?;
+    }
+    // There are none if rcl didn't allocate anything
+    if params.is_null() {
+        return Ok(());
+    }
+
+    collect_node_params(unsafe { &*params }, fully_qualified_name, overrides);
+    unsafe {
+        rcl_yaml_node_struct_fini(params);
+    }
+    Ok(())
+}
+
+/// Returns the paths of the `--params-file` arguments, in the order they were given
+pub(crate) fn param_files(
+    arguments: &rcl_arguments_t,
+    allocator: &dyn Allocator,
+) -> RclResult<Vec<Vec<u8>>> {
+    let count = unsafe { rcl_arguments_get_param_files_count(arguments as *const _) };
+    if count <= 0 {
+        return Ok(vec![]);
+    }
+
+    let rcl_allocator = allocator.rcl_allocator();
+    let mut files: *mut *mut c_char = std::ptr::null_mut();
+    unsafe {
+        rcl_arguments_get_param_files(
+            arguments as *const _,
+            allocator.rcl_allocator(),
+            &mut files as *mut _,
+        )
+        .ok()?;
+    }
+
+    // The paths are kept NUL-terminated, so they can be handed straight back to rcl
+    let deallocate = rcl_allocator.deallocate.unwrap();
+    let paths = unsafe { std::slice::from_raw_parts(files, count as usize) }
+        .iter()
+        .map(|&file| unsafe {
+            let path = CStr::from_ptr(file).to_bytes_with_nul().to_vec();
+            deallocate(file as *mut c_void, rcl_allocator.state);
+            path
+        })
+        .collect();
+    unsafe {
+        deallocate(files as *mut c_void, rcl_allocator.state);
+    }
+
+    Ok(paths)
+}
+
+fn parse_yaml_file(
+    path: &[u8],
+    fully_qualified_name: &str,
+    overrides: &mut HashMap<String, ParameterValue>,
+    allocator: &dyn Allocator,
+) -> RclResult {
+    let params = unsafe { rcl_yaml_node_struct_init(allocator.rcl_allocator()) };
+    if params.is_null() {
+        return Err(RclError::BadAlloc);
+    }
+
+    let result = if unsafe { rcl_parse_yaml_file(path.as_ptr() as *const c_char, params) } {
+        collect_node_params(unsafe { &*params }, fully_qualified_name, overrides);
+        Ok(())
+    } else {
+        record_error_state();
+        Err(RclError::InvalidParamRule)
+    };
+
+    unsafe {
+        rcl_yaml_node_struct_fini(params);
+    }
+    result
+}
+
+/// Adds the parameters in `params` that apply to the node
+fn collect_node_params(
+    params: &rcl_params_t,
+    fully_qualified_name: &str,
+    overrides: &mut HashMap<String, ParameterValue>,
+) {
+    for node_index in 0..params.num_nodes {
+        let node_name = unsafe { c_str_to_string(*params.node_names.add(node_index)) };
+        if !node_name_matches(&node_name, fully_qualified_name) {
+            continue;
+        }
+
+        let node_params = unsafe { &*params.params.add(node_index) };
+        for param_index in 0..node_params.num_params {
+            unsafe {
+                let name = c_str_to_string(*node_params.parameter_names.add(param_index));
+                let variant = &*node_params.parameter_values.add(param_index);
+                if let Some(value) = ParameterValue::from_rcl_variant(variant) {
+                    overrides.insert(name, value);
+                }
+            }
+        }
+    }
+}
+
+/// Whether the parameters under `node_name` in a YAML file apply to the node
+fn node_name_matches(node_name: &str, fully_qualified_name: &str) -> bool {
+    let node_name = node_name.trim_start_matches('/');
+    node_name == "**" || node_name == fully_qualified_name.trim_start_matches('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+
+    fn create_node(node_name: &str) -> Node {
+        Context::default().create_node(node_name).unwrap()
+    }
+
+    /// Descriptor without constraints, the name and type are filled in when declaring
+    fn unconstrained_descriptor() -> ParameterDescriptor {
+        ParameterDescriptor {
+            name: String::new(),
+            parameter_type: ParameterType::Bool,
+            description: String::new(),
+            read_only: false,
+            integer_range: None,
+            floating_point_range: None,
+        }
+    }
+
+    #[test]
+    fn undeclared_parameter_is_gone() -> Result<(), ParameterError> {
+        let node = create_node("undeclare_test");
+        node.declare_parameter("rate", ParameterValue::Integer(10))?;
+        assert!(node.has_parameter("rate"));
+
+        node.undeclare_parameter("rate")?;
+        assert!(!node.has_parameter("rate"));
+        assert_eq!(node.get_parameter("rate"), None);
+        assert_eq!(
+            node.undeclare_parameter("rate"),
+            Err(ParameterError::NotDeclared("rate".to_owned()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn read_only_parameter_cant_be_undeclared() -> Result<(), ParameterError> {
+        let node = create_node("undeclare_read_only_test");
+        let descriptor = ParameterDescriptor {
+            read_only: true,
+            ..unconstrained_descriptor()
+        };
+        node.declare_parameter_with_descriptor("rate", ParameterValue::Integer(10), descriptor)?;
+
+        assert_eq!(
+            node.undeclare_parameter("rate"),
+            Err(ParameterError::ReadOnly("rate".to_owned()))
+        );
+        assert!(node.has_parameter("rate"));
+        Ok(())
+    }
+
+    #[test]
+    fn rejected_change_keeps_old_value() -> Result<(), ParameterError> {
+        let node = create_node("rejected_change_test");
+        node.declare_parameter("rate", ParameterValue::Integer(10))?;
+        node.add_on_set_parameters_callback(|parameters| {
+            let negative = parameters.iter().any(
+                |parameter| matches!(parameter.value, ParameterValue::Integer(value) if value < 0),
+            );
+            SetParametersResult {
+                successful: !negative,
+                reason: if negative {
+                    "rate can't be negative".to_owned()
+                } else {
+                    String::new()
+                },
+            }
+        });
+
+        assert_eq!(
+            node.set_parameter("rate", ParameterValue::Integer(-1)),
+            Err(ParameterError::Rejected(
+                "rate can't be negative".to_owned()
+            ))
+        );
+        assert_eq!(
+            node.get_parameter("rate"),
+            Some(ParameterValue::Integer(10))
+        );
+
+        node.set_parameter("rate", ParameterValue::Integer(20))?;
+        assert_eq!(
+            node.get_parameter("rate"),
+            Some(ParameterValue::Integer(20))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn integer_range_contains() {
+        let range = IntegerRange {
+            from_value: -10,
+            to_value: 10,
+            step: 5,
+        };
+        assert!(range.contains(-10));
+        assert!(range.contains(5));
+        assert!(range.contains(10));
+        assert!(!range.contains(3));
+        assert!(!range.contains(15));
+        assert!(!range.contains(-15));
+    }
+
+    #[test]
+    fn integer_range_without_step_contains() {
+        let range = IntegerRange {
+            from_value: i64::MIN,
+            to_value: i64::MAX,
+            step: 0,
+        };
+        assert!(range.contains(i64::MIN));
+        assert!(range.contains(3));
+        assert!(range.contains(i64::MAX));
+    }
+
+    #[test]
+    fn floating_point_range_contains() {
+        let range = FloatingPointRange {
+            from_value: 0.0,
+            to_value: 1.0,
+            step: 0.1,
+        };
+        assert!(range.contains(0.0));
+        assert!(range.contains(0.3));
+        assert!(range.contains(1.0));
+        assert!(!range.contains(0.25));
+        assert!(!range.contains(1.1));
+        assert!(!range.contains(-0.1));
+
+        let range = FloatingPointRange { step: 0.0, ..range };
+        assert!(range.contains(0.25));
+        assert!(!range.contains(f64::NAN));
+    }
+
+    #[test]
+    fn list_and_describe_parameters() -> Result<(), ParameterError> {
+        let node = create_node("describe_test");
+        let descriptor = ParameterDescriptor {
+            description: "Loop frequency in Hz".to_owned(),
+            integer_range: Some(IntegerRange {
+                from_value: 1,
+                to_value: 100,
+                step: 0,
+            }),
+            ..unconstrained_descriptor()
+        };
+        node.declare_parameter_with_descriptor("rate", ParameterValue::Integer(10), descriptor)?;
+        node.declare_parameter("name", ParameterValue::String("robot".to_owned()))?;
+        assert_eq!(node.list_parameters(), vec!["name", "rate"]);
+
+        let descriptor = node.describe_parameter("rate")?;
+        assert_eq!(descriptor.name, "rate");
+        assert_eq!(descriptor.parameter_type, ParameterType::Integer);
+        assert_eq!(descriptor.description, "Loop frequency in Hz");
+        assert_eq!(
+            node.set_parameter("rate", ParameterValue::Integer(0)),
+            Err(ParameterError::OutOfRange("rate".to_owned()))
+        );
+        assert_eq!(
+            node.describe_parameter("missing"),
+            Err(ParameterError::NotDeclared("missing".to_owned()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn typed_parameter_gets_and_sets() -> Result<(), ParameterError> {
+        let node = create_node("typed_parameter_test");
+        let rate = node.declare_typed_parameter("rate", 10_i64)?;
+        assert_eq!(rate.name(), "rate");
+        assert_eq!(rate.get(), Some(10));
+
+        rate.set(20)?;
+        assert_eq!(rate.get(), Some(20));
+        node.set_parameter("rate", ParameterValue::Integer(30))?;
+        assert_eq!(rate.get(), Some(30));
+
+        assert_eq!(
+            node.set_parameter("rate", ParameterValue::Double(1.5)),
+            Err(ParameterError::TypeMismatch {
+                name: "rate".to_owned(),
+                expected: ParameterType::Integer,
+                actual: ParameterType::Double,
+            })
+        );
+        assert_eq!(rate.get(), Some(30));
+
+        node.undeclare_parameter("rate")?;
+        assert_eq!(rate.get(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn node_name_matches_fully_qualified_name() {
+        assert!(node_name_matches("/**", "/ns/my_node"));
+        assert!(node_name_matches("**", "/my_node"));
+        assert!(node_name_matches("my_node", "/my_node"));
+        assert!(node_name_matches("/ns/my_node", "/ns/my_node"));
+        assert!(!node_name_matches("my_node", "/ns/my_node"));
+        assert!(!node_name_matches("/other_node", "/my_node"));
+    }
+}
@@ -1,22 +1,92 @@
-use crate::error::{RclResult, ToRclResult};
+use super::c_str_to_string;
+use super::graph::wait_for_graph_condition;
+use crate::error::{PublishError, RclError, RclResult, ToRclResult};
 use crate::qos::QoSProfile;
-use crate::{Handle, Node, NodeHandle};
+use crate::{Allocator, Clock, Handle, Node, NodeHandle};
 use rcl_sys::*;
 use std::borrow::Borrow;
 use std::cell::{Ref, RefCell, RefMut};
 use std::ffi::CString;
 use std::marker::PhantomData;
 use std::rc::Rc;
+use std::time::Duration;
 
 pub struct PublisherHandle {
     handle: RefCell<rcl_publisher_t>,
     node_handle: Rc<NodeHandle>,
+    allocator: Rc<dyn Allocator>,
 }
 
 impl PublisherHandle {
+    pub(crate) fn new(
+        node: &Node,
+        type_support: *const rosidl_message_type_support_t,
+        topic: &str,
+        qos: QoSProfile,
+    ) -> RclResult<Self> {
+        node.ensure_valid()?;
+        qos.validate()?;
+        let mut publisher_handle = unsafe { rcl_get_zero_initialized_publisher() };
+        let topic_c_string = CString::new(topic).map_err(|_| RclError::TopicNameInvalid)?;
+        let node_handle = &mut *node.handle.get_mut();
+
+        unsafe {
+            let mut publisher_options = rcl_publisher_get_default_options();
+            publisher_options.qos = qos.into();
+            publisher_options.allocator = node.allocator.rcl_allocator();
+
+            rcl_publisher_init(
+                &mut publisher_handle as *mut _,
+                node_handle as *mut _,
+                type_support,
+                topic_c_string.as_ptr(),
+                &publisher_options as *const _,
+            )
+            .ok()?;
+        }
+
+        Ok(Self {
+            handle: RefCell::new(publisher_handle),
+            node_handle: node.handle.clone(),
+            allocator: node.allocator.clone(),
+        })
+    }
+
     fn node_handle(&self) -> &NodeHandle {
         self.node_handle.borrow()
     }
+
+    pub(crate) fn subscription_count(&self) -> RclResult<usize> {
+        let handle = &*self.get();
+        let mut count = 0;
+        unsafe {
+            rcl_publisher_get_subscription_count(handle as *const _, &mut count as *mut _).ok()?;
+        }
+        Ok(count)
+    }
+
+    pub(crate) fn topic_name(&self) -> String {
+        let handle = &*self.get();
+        unsafe { c_str_to_string(rcl_publisher_get_topic_name(handle as *const _)) }
+    }
+
+    pub(crate) fn publish_serialized(&self, buffer: &[u8]) -> RclResult {
+        let serialized_message = rcl_serialized_message_t {
+            buffer: buffer.as_ptr() as *mut _,
+            buffer_length: buffer.len(),
+            buffer_capacity: buffer.len(),
+            allocator: self.allocator.rcl_allocator(),
+        };
+        let handle = &*self.get();
+        unsafe {
+            rcl_publish_serialized_message(
+                handle as *const _,
+                &serialized_message as *const _,
+                std::ptr::null_mut(),
+            )
+            .ok()
+        }
+    }
 }
 
 impl<'a> Handle<rcl_publisher_t> for &'a PublisherHandle {
@@ -43,11 +113,17 @@ impl Drop for PublisherHandle {
 }
 
 /// Main class responsible for publishing data to ROS topics
+///
+/// `rcl_publish` is thread-safe for a single publisher, but a publisher shares its node and
+/// allocator through `Rc`s, the same as every other entity here. It is therefore neither `Send`
+/// nor `Sync` and has to be used from the thread that owns its node; an `unsafe impl` would let
+/// those reference counts race.
 pub struct Publisher<T>
 where
     T: rclrs_common::traits::MessageDefinition<T>,
 {
     pub handle: Rc<PublisherHandle>,
+    clock: Rc<Clock>,
     message: PhantomData<T>,
 }
 
@@ -59,37 +135,20 @@ where
     where
         T: rclrs_common::traits::MessageDefinition<T>,
     {
-        let mut publisher_handle = unsafe { rcl_get_zero_initialized_publisher() };
         let type_support = T::get_type_support() as *const rosidl_message_type_support_t;
-        let topic_c_string = CString::new(topic).unwrap();
-        let node_handle = &mut *node.handle.get_mut();
-
-        unsafe {
-            let mut publisher_options = rcl_publisher_get_default_options();
-            publisher_options.qos = qos.into();
-
-            rcl_publisher_init(
-                &mut publisher_handle as *mut _,
-                node_handle as *mut _,
-                type_support,
-                topic_c_string.as_ptr(),
-                &publisher_options as *const _,
-            )
-            .ok()?;
-        }
-
-        let handle = Rc::new(PublisherHandle {
-            handle: RefCell::new(publisher_handle),
-            node_handle: node.handle.clone(),
-        });
+        let handle = Rc::new(PublisherHandle::new(node, type_support, topic, qos)?);
+        node.register_local_publisher(&handle, T::TYPE_NAME.to_owned());
 
         Ok(Self {
             handle,
+            clock: node.get_clock(),
             message: PhantomData,
         })
     }
 
-    pub fn publish(&self, message: &T) -> RclResult {
+    /// Publishes `message`, after checking that its bounded fields are within their bounds
+    pub fn publish(&self, message: &T) -> Result<(), PublishError> {
+        message.validate()?;
         let native_message_ptr = message.get_native_message();
         let handle = &mut *self.handle.get_mut();
         let ret = unsafe {
@@ -100,6 +159,64 @@ where
             )
         };
         message.destroy_native_message(native_message_ptr);
-        ret.ok()
+        if ret as u32 == RCL_RET_OK {
+            Ok(())
+        } else {
+            Err(PublishError::from_rcl_ret(ret))
+        }
+    }
+
+    /// Sets the header stamp of `message` to the current time of the node's clock, then
+    /// publishes it
+    ///
+    /// Uses the ROS time of the node, i.e. the simulated time when `use_sim_time` is set.
+    pub fn publish_stamped(&self, message: &mut T) -> Result<(), PublishError>
+    where
+        T: rclrs_common::traits::Stamped,
+    {
+        message.set_stamp(self.clock.now()?.nanoseconds);
+        self.publish(message)
+    }
+
+    /// Manually asserts that the publisher is alive
+    ///
+    /// Only needed with [`crate::QoSLivelinessPolicy::ManualByTopic`], where publishing a
+    /// message asserts liveliness as well.
+    pub fn assert_liveliness(&self) -> RclResult {
+        let handle = &*self.handle.get();
+        unsafe { rcl_publisher_assert_liveliness(handle as *const _).ok() }
+    }
+
+    /// Returns the fully qualified name of the topic, after remapping, e.g. `"/ns/chatter"`
+    pub fn topic_name(&self) -> String {
+        self.handle.topic_name()
+    }
+
+    /// Returns the number of subscriptions currently matched with the publisher
+    ///
+    /// Unlike [`Node::count_subscribers`], this only counts subscriptions whose QoS is
+    /// compatible with the publisher's.
+    pub fn subscription_count(&self) -> RclResult<usize> {
+        self.handle.subscription_count()
+    }
+
+    /// Blocks until at least one subscription is matched or `timeout` elapses, returns whether
+    /// one was matched
+    ///
+    /// Useful to keep the first messages from getting lost before anyone listens.
+    pub fn wait_for_subscription(&self, timeout: Duration) -> RclResult<bool> {
+        wait_for_graph_condition(
+            self.handle.node_handle(),
+            &*self.handle.allocator,
+            timeout,
+            || Ok(self.handle.subscription_count()? > 0),
+        )
+    }
+
+    /// Publishes a message that has already been serialized by the middleware (e.g. CDR)
+    ///
+    /// The buffer must hold a serialized message of type `T`; it is handed to rcl without copying.
+    pub fn publish_serialized(&self, buffer: &[u8]) -> RclResult {
+        self.handle.publish_serialized(buffer)
     }
 }
@@ -0,0 +1,108 @@
+use crate::error::RclResult;
+use crate::qos::QOS_PROFILE_DEFAULT;
+use crate::{Node, Subscription, SubscriptionBase};
+use rcl_sys::*;
+use rclrs_common::traits::MessageDefinition;
+use std::rc::{Rc, Weak};
+
+#[link(name = "rosgraph_msgs__rosidl_typesupport_c")]
+extern "C" {
+    fn rosidl_typesupport_c__get_message_type_support_handle__rosgraph_msgs__msg__Clock(
+    ) -> *const rosidl_message_type_support_t;
+}
+
+/// Layout of the C `rosgraph_msgs__msg__Clock`, which only holds a `builtin_interfaces/Time`
+#[repr(C)]
+#[derive(Default)]
+struct NativeClockMessage {
+    sec: i32,
+    nanosec: u32,
+}
+
+/// Message published on `/clock` by simulators and bag playback
+#[derive(Default)]
+pub(crate) struct ClockMessage {
+    nanoseconds: i64,
+}
+
+impl rclrs_common::traits::Message for ClockMessage {
+    fn get_native_message(&self) -> usize {
+        Self::static_get_native_message(self)
+    }
+
+    fn destroy_native_message(&self, message_handle: usize) {
+        Self::static_destroy_native_message(message_handle);
+    }
+
+    fn read_handle(&mut self, message_handle: usize) {
+        let native = unsafe { &*(message_handle as *const NativeClockMessage) };
+        self.nanoseconds = i64::from(native.sec) * 1_000_000_000 + i64::from(native.nanosec);
+    }
+}
+
+impl MessageDefinition<ClockMessage> for ClockMessage {
+    const TYPE_NAME: &'static str = "rosgraph_msgs/msg/Clock";
+
+    fn get_type_support() -> usize {
+        let type_support = unsafe {
+            rosidl_typesupport_c__get_message_type_support_handle__rosgraph_msgs__msg__Clock()
+        };
+        type_support as usize
+    }
+
+    fn static_get_native_message(message: &ClockMessage) -> usize {
+        let native = Box::new(NativeClockMessage {
+            sec: (message.nanoseconds / 1_000_000_000) as i32,
+            nanosec: (message.nanoseconds % 1_000_000_000) as u32,
+        });
+        Box::into_raw(native) as usize
+    }
+
+    fn static_destroy_native_message(message_handle: usize) {
+        drop(unsafe { Box::from_raw(message_handle as *mut NativeClockMessage) });
+    }
+}
+
+impl Node {
+    /// Whether the node's clock follows `/clock` instead of system time
+    pub fn use_sim_time(&self) -> bool {
+        self.sim_time_subscription.borrow().is_some()
+    }
+
+    /// Switches the node's clock between simulated time from `/clock` and system time
+    ///
+    /// Simulated time starts once the first `/clock` message is handled by [`crate::spin`].
+    pub fn set_use_sim_time(&self, enable: bool) -> RclResult {
+        if enable == self.use_sim_time() {
+            return Ok(());
+        }
+
+        if enable {
+            let clock = self.clock.clone();
+            let subscription = Rc::new(Subscription::<ClockMessage>::new(
+                self,
+                "/clock",
+                QOS_PROFILE_DEFAULT,
+                move |message: &ClockMessage| {
+                    // A failure here only means the clock keeps its previous time
+                    let _ = clock.set_ros_time_override(message.nanoseconds);
+                },
+            )?) as Rc<dyn SubscriptionBase>;
+            self.clock.enable_ros_time_override()?;
+            self.subscriptions
+                .borrow_mut()
+                .push(Rc::downgrade(&subscription));
+            *self.sim_time_subscription.borrow_mut() = Some(subscription);
+        } else {
+            self.clock.disable_ros_time_override()?;
+            if let Some(subscription) = self.sim_time_subscription.borrow_mut().take() {
+                let subscription = Rc::downgrade(&subscription);
+                self.subscriptions
+                    .borrow_mut()
+                    .retain(|other| !Weak::ptr_eq(other, &subscription));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use crate::error::{RclError, RclResult};
+use crate::qos::QoSProfile;
+use crate::validation::validate_namespace;
+use crate::{
+    DynamicPublisher, DynamicSubscription, MessageInfo, Node, Publisher, Subscription,
+    SubscriptionOptions,
+};
+use std::rc::Rc;
+
+/// A view on a node that puts the topics of the entities created through it in a
+/// sub-namespace, like `create_sub_node` in rclcpp
+///
+/// Everything is created on the same rcl node and registered with it, so spinning the node
+/// services the entities created through its sub-nodes as well. Only relative topic names are
+/// extended; absolute ones and private ones starting with `~` are used as they are.
+///
+/// ```ignore
+/// let node = Node::new_with_namespace("my_node", "/base", &context)?;
+/// let sensors = node.create_sub_node("sensors")?;
+/// // Publishes on /base/sensors/topic
+/// let publisher =
+///     sensors.create_publisher::<std_msgs::msg::String>("topic", QOS_PROFILE_DEFAULT)?;
+/// ```
+pub struct SubNode<'node> {
+    node: &'node Node,
+    sub_namespace: String,
+}
+
+impl Node {
+    /// Returns a view on this node whose entities' topics are in `sub_namespace`
+    ///
+    /// The sub-namespace has to be relative, e.g. `"sensors"` or `"sensors/front"`, and is
+    /// refused with [`RclError::NodeInvalidNamespace`] otherwise.
+    pub fn create_sub_node(&self, sub_namespace: &str) -> RclResult<SubNode<'_>> {
+        SubNode::new(self, sub_namespace.to_owned())
+    }
+}
+
+impl<'node> SubNode<'node> {
+    fn new(node: &'node Node, sub_namespace: String) -> RclResult<Self> {
+        if sub_namespace.is_empty() || sub_namespace.starts_with('/') {
+            return Err(RclError::NodeInvalidNamespace);
+        }
+        validate_namespace(&format!("/{}", sub_namespace))
+            .map_err(|_| RclError::NodeInvalidNamespace)?;
+        Ok(Self {
+            node,
+            sub_namespace,
+        })
+    }
+
+    /// Returns the node the entities are created on
+    pub fn node(&self) -> &'node Node {
+        self.node
+    }
+
+    /// Returns the sub-namespace, relative to the node's namespace
+    pub fn sub_namespace(&self) -> &str {
+        &self.sub_namespace
+    }
+
+    /// Returns a view on the same node that extends this sub-namespace further
+    pub fn create_sub_node(&self, sub_namespace: &str) -> RclResult<SubNode<'node>> {
+        if sub_namespace.starts_with('/') {
+            return Err(RclError::NodeInvalidNamespace);
+        }
+        SubNode::new(
+            self.node,
+            format!("{}/{}", self.sub_namespace, sub_namespace),
+        )
+    }
+
+    /// Like [`Node::resolve_topic_name`], for a topic in the sub-namespace
+    pub fn resolve_topic_name(&self, topic: &str) -> RclResult<String> {
+        self.node.resolve_topic_name(&self.extend_name(topic))
+    }
+
+    pub fn create_publisher<T>(&self, topic: &str, qos: QoSProfile) -> RclResult<Publisher<T>>
+    where
+        T: rclrs_common::traits::MessageDefinition<T>,
+    {
+        self.node.create_publisher(&self.extend_name(topic), qos)
+    }
+
+    pub fn create_dynamic_publisher(
+        &self,
+        topic: &str,
+        type_name: &str,
+        qos: QoSProfile,
+    ) -> RclResult<DynamicPublisher> {
+        self.node
+            .create_dynamic_publisher(&self.extend_name(topic), type_name, qos)
+    }
+
+    pub fn create_dynamic_subscription(
+        &self,
+        topic: &str,
+        type_name: &str,
+        qos: QoSProfile,
+    ) -> RclResult<DynamicSubscription> {
+        self.node
+            .create_dynamic_subscription(&self.extend_name(topic), type_name, qos)
+    }
+
+    pub fn create_subscription<T, F>(
+        &self,
+        topic: &str,
+        qos: QoSProfile,
+        callback: F,
+    ) -> RclResult<Rc<Subscription<T>>>
+    where
+        T: rclrs_common::traits::MessageDefinition<T> + Default,
+        F: FnMut(&T) + Sized + 'static,
+    {
+        self.node
+            .create_subscription(&self.extend_name(topic), qos, callback)
+    }
+
+    pub fn create_subscription_with_options<T, F>(
+        &self,
+        topic: &str,
+        qos: QoSProfile,
+        options: SubscriptionOptions,
+        callback: F,
+    ) -> RclResult<Rc<Subscription<T>>>
+    where
+        T: rclrs_common::traits::MessageDefinition<T> + Default,
+        F: FnMut(&T) + Sized + 'static,
+    {
+        self.node
+            .create_subscription_with_options(&self.extend_name(topic), qos, options, callback)
+    }
+
+    pub fn create_subscription_with_info<T, F>(
+        &self,
+        topic: &str,
+        qos: QoSProfile,
+        callback: F,
+    ) -> RclResult<Rc<Subscription<T>>>
+    where
+        T: rclrs_common::traits::MessageDefinition<T> + Default,
+        F: FnMut(&T, &MessageInfo) + Sized + 'static,
+    {
+        self.node
+            .create_subscription_with_info(&self.extend_name(topic), qos, callback)
+    }
+
+    pub fn create_polling_subscription<T>(
+        &self,
+        topic: &str,
+        qos: QoSProfile,
+    ) -> RclResult<Rc<Subscription<T>>>
+    where
+        T: rclrs_common::traits::MessageDefinition<T> + Default,
+    {
+        self.node
+            .create_polling_subscription(&self.extend_name(topic), qos)
+    }
+
+    fn extend_name(&self, name: &str) -> String {
+        if name.starts_with('/') || name.starts_with('~') {
+            name.to_owned()
+        } else {
+            format!("{}/{}", self.sub_namespace, name)
+        }
+    }
+}
@@ -1,22 +1,130 @@
+use super::c_str_to_string;
+use super::graph::wait_for_graph_condition;
 use crate::error::{RclError, RclResult, ToRclResult};
 use crate::qos::QoSProfile;
-use crate::{Handle, Node, NodeHandle};
+use crate::{Allocator, Handle, Node, NodeHandle};
 use rcl_sys::*;
 use std::borrow::Borrow;
-use std::cell::{Ref, RefCell, RefMut};
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::collections::VecDeque;
 use std::ffi::CString;
 use std::marker::PhantomData;
 use std::rc::Rc;
+use std::time::Duration;
+
+/// Settings of a subscription besides its QoS
+#[derive(Clone, Debug, Default)]
+pub struct SubscriptionOptions {
+    /// Don't deliver messages published by the subscription's own node
+    ///
+    /// Not every rmw implementation supports this; the ones that don't still deliver those
+    /// messages, which can be told apart through [`MessageInfo::publisher_gid`].
+    pub ignore_local_publications: bool,
+}
 
 pub struct SubscriptionHandle {
     handle: RefCell<rcl_subscription_t>,
     node_handle: Rc<NodeHandle>,
+    allocator: Rc<dyn Allocator>,
 }
 
 impl SubscriptionHandle {
+    pub(crate) fn new(
+        node: &Node,
+        type_support: *const rosidl_message_type_support_t,
+        topic: &str,
+        qos: QoSProfile,
+        options: &SubscriptionOptions,
+    ) -> RclResult<Self> {
+        node.ensure_valid()?;
+        qos.validate()?;
+        let mut subscription_handle = unsafe { rcl_get_zero_initialized_subscription() };
+        let topic_c_string = CString::new(topic).map_err(|_| RclError::TopicNameInvalid)?;
+        let node_handle = &mut *node.handle.get_mut();
+
+        unsafe {
+            let mut subscription_options = rcl_subscription_get_default_options();
+            subscription_options.qos = qos.into();
+            subscription_options.allocator = node.allocator.rcl_allocator();
+            subscription_options
+                .rmw_subscription_options
+                .ignore_local_publications = options.ignore_local_publications;
+            rcl_subscription_init(
+                &mut subscription_handle as *mut _,
+                node_handle as *mut _,
+                type_support,
+                topic_c_string.as_ptr(),
+                &subscription_options as *const _,
+            )
+            .ok()?;
+        }
+
+        Ok(Self {
+            handle: RefCell::new(subscription_handle),
+            node_handle: node.handle.clone(),
+            allocator: node.allocator.clone(),
+        })
+    }
+
     fn node_handle(&self) -> &NodeHandle {
         self.node_handle.borrow()
     }
+
+    pub(crate) fn publisher_count(&self) -> RclResult<usize> {
+        let handle = &*self.get();
+        let mut count = 0;
+        unsafe {
+            rcl_subscription_get_publisher_count(handle as *const _, &mut count as *mut _).ok()?;
+        }
+        Ok(count)
+    }
+
+    pub(crate) fn topic_name(&self) -> String {
+        let handle = &*self.get();
+        unsafe { c_str_to_string(rcl_subscription_get_topic_name(handle as *const _)) }
+    }
+
+    pub(crate) fn take_serialized(&self) -> RclResult<Option<Vec<u8>>> {
+        let handle = &*self.get();
+        let mut serialized_message = unsafe { rcutils_get_zero_initialized_uint8_array() };
+
+        unsafe {
+            // The middleware resizes the buffer to fit the message being taken
+            let allocator = self.allocator.rcl_allocator();
+            rcutils_uint8_array_init(&mut serialized_message as *mut _, 0, &allocator as *const _)
+                .ok()?;
+        }
+
+        let result = unsafe {
+            rcl_take_serialized_message(
+                handle as *const _,
+                &mut serialized_message as *mut _,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+
+        let result = match result.into() {
+            RclError::Ok if serialized_message.buffer_length == 0 => Ok(Some(vec![])),
+            RclError::Ok => Ok(Some(
+                unsafe {
+                    std::slice::from_raw_parts(
+                        serialized_message.buffer,
+                        serialized_message.buffer_length,
+                    )
+                }
+                .to_vec(),
+            )),
+            RclError::SubscriptionTakeFailed => Ok(None),
+            error => Err(error),
+        };
+
+        unsafe {
+            rcutils_uint8_array_fini(&mut serialized_message as *mut _).ok()?;
+        }
+
+        result
+    }
 }
 
 impl<'a> Handle<rcl_subscription_t> for &'a SubscriptionHandle {
@@ -42,12 +150,39 @@ impl Drop for SubscriptionHandle {
     }
 }
 
+/// Metadata delivered by the middleware alongside a taken message
+pub struct MessageInfo {
+    /// Time at which the message was published, in nanoseconds since the epoch
+    pub source_timestamp: i64,
+    /// Time at which the message was received, in nanoseconds since the epoch
+    pub received_timestamp: i64,
+    /// Global identifier of the publisher that sent the message
+    pub publisher_gid: [u8; RMW_GID_STORAGE_SIZE as usize],
+    /// Whether the message was delivered through intra-process communication
+    pub from_intra_process: bool,
+}
+
+impl From<rmw_message_info_t> for MessageInfo {
+    fn from(message_info: rmw_message_info_t) -> Self {
+        Self {
+            source_timestamp: message_info.source_timestamp,
+            received_timestamp: message_info.received_timestamp,
+            publisher_gid: message_info.publisher_gid.data,
+            from_intra_process: message_info.from_intra_process,
+        }
+    }
+}
+
 /// Trait to be implemented by concrete Subscriber structs
 /// See [`Subscription<T>`] for an example
 pub trait SubscriptionBase {
     fn handle(&self) -> &SubscriptionHandle;
     fn create_message(&self) -> Box<dyn rclrs_common::traits::Message>;
-    fn callback_fn(&self, message: Box<dyn rclrs_common::traits::Message>);
+    fn callback_fn(
+        &self,
+        message: Box<dyn rclrs_common::traits::Message>,
+        message_info: MessageInfo,
+    );
 
     /// Ask RMW for the data
     ///
@@ -65,14 +200,24 @@ pub trait SubscriptionBase {
     /// |  rmw_take   |
     /// +-------------+
     fn take(&self, message: &mut dyn rclrs_common::traits::Message) -> RclResult<bool> {
+        self.take_with_info(message)
+            .map(|message_info| message_info.is_some())
+    }
+
+    /// Like [`SubscriptionBase::take`], also returning the [`MessageInfo`] of a taken message
+    fn take_with_info(
+        &self,
+        message: &mut dyn rclrs_common::traits::Message,
+    ) -> RclResult<Option<MessageInfo>> {
         let handle = &*self.handle().get();
         let message_handle = message.get_native_message();
+        let mut message_info = unsafe { rmw_get_zero_initialized_message_info() };
 
         let result = unsafe {
             rcl_take(
                 handle as *const _,
                 message_handle as *mut _,
-                std::ptr::null_mut(),
+                &mut message_info as *mut _,
                 std::ptr::null_mut(),
             )
         };
@@ -80,9 +225,9 @@ pub trait SubscriptionBase {
         let result = match result.into() {
             RclError::Ok => {
                 message.read_handle(message_handle);
-                Ok(true)
+                Ok(Some(MessageInfo::from(message_info)))
             }
-            RclError::SubscriptionTakeFailed => Ok(false),
+            RclError::SubscriptionTakeFailed => Ok(None),
             error => Err(error),
         };
 
@@ -92,14 +237,48 @@ pub trait SubscriptionBase {
     }
 }
 
+/// Handle of a native message, with the function that frees it
+type NativeMessage = (usize, fn(usize));
+
+/// A callback that takes the [`MessageInfo`] along with the message
+pub type MessageInfoCallback<T> = Box<dyn FnMut(&T, &MessageInfo) + 'static>;
+
+/// The callback of a [`Subscription`], which may take the [`MessageInfo`] as well
+pub enum SubscriptionCallback<T> {
+    Message(Box<dyn FnMut(&T) + 'static>),
+    /// Set through [`Node::create_subscription_with_info`] or
+    /// [`Subscription::set_callback_with_info`], e.g. to measure latencies
+    WithInfo(MessageInfoCallback<T>),
+}
+
+impl<T> SubscriptionCallback<T> {
+    fn call(&mut self, message: &T, message_info: &MessageInfo) {
+        match self {
+            SubscriptionCallback::Message(callback) => callback(message),
+            SubscriptionCallback::WithInfo(callback) => callback(message, message_info),
+        }
+    }
+}
+
 /// Main class responsible for subscribing to topics and receiving data over IPC in ROS
+///
+/// Like [`crate::Publisher`], a subscription is bound to the thread that owns its node. Taking
+/// from one subscription concurrently is not safe in rcl either, which is why its handle is
+/// only ever borrowed through a `RefCell`.
 pub struct Subscription<T>
 where
     T: rclrs_common::traits::Message,
 {
     pub handle: Rc<SubscriptionHandle>,
     // The callback's lifetime should last as long as we need it to
-    pub callback: RefCell<Box<dyn FnMut(&T) + 'static>>,
+    pub callback: RefCell<SubscriptionCallback<T>>,
+    /// Set by [`Subscription::set_callback`] while the callback is running
+    next_callback: RefCell<Option<SubscriptionCallback<T>>>,
+    /// Native message reused by [`Subscription::take_into`], with the function that frees it
+    native_message: Cell<Option<NativeMessage>>,
+    /// The last messages passed to the callback, oldest first, see [`Subscription::keep_history`]
+    history: RefCell<VecDeque<T>>,
+    history_capacity: Cell<usize>,
     message: PhantomData<T>,
 }
 
@@ -112,32 +291,78 @@ where
         T: rclrs_common::traits::MessageDefinition<T>,
         F: FnMut(&T) + Sized + 'static,
     {
-        let mut subscription_handle = unsafe { rcl_get_zero_initialized_subscription() };
-        let type_support = T::get_type_support() as *const rosidl_message_type_support_t;
-        let topic_c_string = CString::new(topic).unwrap();
-        let node_handle = &mut *node.handle.get_mut();
+        Self::new_with_options(node, topic, qos, SubscriptionOptions::default(), callback)
+    }
 
-        unsafe {
-            let mut subscription_options = rcl_subscription_get_default_options();
-            subscription_options.qos = qos.into();
-            rcl_subscription_init(
-                &mut subscription_handle as *mut _,
-                node_handle as *mut _,
-                type_support,
-                topic_c_string.as_ptr(),
-                &subscription_options as *const _,
-            )
-            .ok()?;
-        }
+    pub fn new_with_options<F>(
+        node: &Node,
+        topic: &str,
+        qos: QoSProfile,
+        options: SubscriptionOptions,
+        callback: F,
+    ) -> RclResult<Self>
+    where
+        T: rclrs_common::traits::MessageDefinition<T>,
+        F: FnMut(&T) + Sized + 'static,
+    {
+        Self::new_with_callback(
+            node,
+            topic,
+            qos,
+            options,
+            SubscriptionCallback::Message(Box::new(callback)),
+        )
+    }
 
-        let handle = Rc::new(SubscriptionHandle {
-            handle: RefCell::new(subscription_handle),
-            node_handle: node.handle.clone(),
-        });
+    /// Like [`Subscription::new_with_options`], for a callback that takes the
+    /// [`MessageInfo`] as well
+    pub fn new_with_info<F>(
+        node: &Node,
+        topic: &str,
+        qos: QoSProfile,
+        options: SubscriptionOptions,
+        callback: F,
+    ) -> RclResult<Self>
+    where
+        T: rclrs_common::traits::MessageDefinition<T>,
+        F: FnMut(&T, &MessageInfo) + Sized + 'static,
+    {
+        Self::new_with_callback(
+            node,
+            topic,
+            qos,
+            options,
+            SubscriptionCallback::WithInfo(Box::new(callback)),
+        )
+    }
+
+    fn new_with_callback(
+        node: &Node,
+        topic: &str,
+        qos: QoSProfile,
+        options: SubscriptionOptions,
+        callback: SubscriptionCallback<T>,
+    ) -> RclResult<Self>
+    where
+        T: rclrs_common::traits::MessageDefinition<T>,
+    {
+        let type_support = T::get_type_support() as *const rosidl_message_type_support_t;
+        let handle = Rc::new(SubscriptionHandle::new(
+            node,
+            type_support,
+            topic,
+            qos,
+            &options,
+        )?);
+        node.register_local_subscription(&handle, T::TYPE_NAME.to_owned());
 
         Ok(Self {
             handle,
-            callback: RefCell::new(Box::new(callback)),
+            callback: RefCell::new(callback),
+            next_callback: RefCell::new(None),
+            native_message: Cell::new(None),
+            history: RefCell::new(VecDeque::new()),
+            history_capacity: Cell::new(0),
             message: PhantomData,
         })
     }
@@ -158,9 +383,250 @@ where
         ret.ok()
     }
 
-    fn callback_ext(&self, message: Box<dyn rclrs_common::traits::Message>) {
+    /// Takes the next message into `message`, returns whether one was available
+    ///
+    /// Unlike [`Subscription::take`], the native message is allocated once and reused by every
+    /// later call, and the strings and sequences of `message` keep their storage when they
+    /// still fit. Taking messages of a bounded size into the same `message` over and over
+    /// therefore doesn't allocate once their sizes have settled.
+    pub fn take_into(&self, message: &mut T) -> RclResult<bool>
+    where
+        T: rclrs_common::traits::MessageDefinition<T>,
+    {
+        let message_handle = match self.native_message.get() {
+            Some((message_handle, _)) => message_handle,
+            None => {
+                let message_handle = T::static_get_native_message(message);
+                self.native_message
+                    .set(Some((message_handle, T::static_destroy_native_message)));
+                message_handle
+            }
+        };
+
+        let handle = &*self.handle.get();
+        let result = unsafe {
+            rcl_take(
+                handle as *const _,
+                message_handle as *mut _,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+
+        match result.into() {
+            RclError::Ok => {
+                message.read_handle(message_handle);
+                Ok(true)
+            }
+            RclError::SubscriptionTakeFailed => Ok(false),
+            error => Err(error),
+        }
+    }
+
+    /// Takes the next message together with its [`MessageInfo`]
+    ///
+    /// Returns `None` if no message was available.
+    pub fn take_with_info(&self) -> RclResult<Option<(T, MessageInfo)>>
+    where
+        T: Default,
+    {
+        let handle = &*self.handle.get();
+        let mut message = T::default();
+        let message_handle = message.get_native_message();
+        let mut message_info = unsafe { rmw_get_zero_initialized_message_info() };
+
+        let result = unsafe {
+            rcl_take(
+                handle as *const _,
+                message_handle as *mut _,
+                &mut message_info as *mut _,
+                std::ptr::null_mut(),
+            )
+        };
+
+        let result = match result.into() {
+            RclError::Ok => {
+                message.read_handle(message_handle);
+                Ok(true)
+            }
+            RclError::SubscriptionTakeFailed => Ok(false),
+            error => Err(error),
+        };
+
+        message.destroy_native_message(message_handle);
+
+        match result {
+            Ok(true) => Ok(Some((message, MessageInfo::from(message_info)))),
+            Ok(false) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Takes every message currently queued on the subscription
+    ///
+    /// At most as many messages as the history depth are taken, so a publisher that keeps up
+    /// with the loop can't keep it running forever.
+    pub fn take_all(&self) -> RclResult<Vec<T>>
+    where
+        T: Default,
+    {
+        let depth = {
+            let handle = &*self.handle.get();
+            unsafe { rcl_subscription_get_options(handle as *const _).as_ref() }
+                .map(|options| options.qos.depth)
+                .filter(|&depth| depth > 0)
+                .unwrap_or(usize::MAX)
+        };
+
+        let mut messages = vec![];
+        while messages.len() < depth {
+            match self.take_with_info()? {
+                Some((message, _)) => messages.push(message),
+                None => break,
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Waits for the next message without blocking the async runtime
+    ///
+    /// The subscription is polled every `poll_period` until a message arrives. A message
+    /// received this way is not passed to the callback, so avoid mixing this with [`crate::spin`]
+    /// on the same subscription.
+    #[cfg(feature = "tokio")]
+    pub async fn recv(&self, poll_period: std::time::Duration) -> RclResult<(T, MessageInfo)>
+    where
+        T: Default,
+    {
+        loop {
+            if let Some(message) = self.take_with_info()? {
+                return Ok(message);
+            }
+            tokio::time::sleep(poll_period).await;
+        }
+    }
+
+    /// Returns the fully qualified name of the topic, after remapping, e.g. `"/ns/chatter"`
+    pub fn topic_name(&self) -> String {
+        self.handle.topic_name()
+    }
+
+    /// Returns the number of publishers currently matched with the subscription
+    ///
+    /// Unlike [`Node::count_publishers`], this only counts publishers whose QoS is compatible
+    /// with the subscription's.
+    pub fn publisher_count(&self) -> RclResult<usize> {
+        self.handle.publisher_count()
+    }
+
+    /// Blocks until at least one publisher is matched or `timeout` elapses, returns whether
+    /// one was matched
+    ///
+    /// Together with [`crate::Publisher::wait_for_subscription`] this makes sure both ends are
+    /// connected before the first message is sent, instead of sleeping for discovery.
+    pub fn wait_for_publisher(&self, timeout: Duration) -> RclResult<bool> {
+        wait_for_graph_condition(
+            self.handle.node_handle(),
+            &*self.handle.allocator,
+            timeout,
+            || Ok(self.handle.publisher_count()? > 0),
+        )
+    }
+
+    /// Takes the next message as a buffer serialized by the middleware (e.g. CDR)
+    ///
+    /// Returns `None` if no message was available.
+    pub fn take_serialized(&self) -> RclResult<Option<Vec<u8>>> {
+        self.handle.take_serialized()
+    }
+
+    /// Replaces the callback, starting with the next message
+    ///
+    /// Callbacks only run while the node is spun on the thread that owns the subscription, so
+    /// no lock is needed. This may also be called from within the callback itself, the
+    /// replacement then takes over once the running callback returns.
+    pub fn set_callback<F>(&self, callback: F)
+    where
+        F: FnMut(&T) + Sized + 'static,
+    {
+        self.replace_callback(SubscriptionCallback::Message(Box::new(callback)));
+    }
+
+    /// Like [`Subscription::set_callback`], for a callback that takes the [`MessageInfo`] as
+    /// well
+    pub fn set_callback_with_info<F>(&self, callback: F)
+    where
+        F: FnMut(&T, &MessageInfo) + Sized + 'static,
+    {
+        self.replace_callback(SubscriptionCallback::WithInfo(Box::new(callback)));
+    }
+
+    fn replace_callback(&self, callback: SubscriptionCallback<T>) {
+        match self.callback.try_borrow_mut() {
+            Ok(mut current_callback) => *current_callback = callback,
+            Err(_) => *self.next_callback.borrow_mut() = Some(callback),
+        }
+    }
+
+    /// Keeps the last `capacity` messages passed to the callback, zero to keep none
+    ///
+    /// The messages are moved into the history after the callback returns, so it only covers
+    /// messages received while spinning, not the ones taken directly. Lowering the capacity
+    /// drops the oldest messages right away.
+    pub fn keep_history(&self, capacity: usize) {
+        self.history_capacity.set(capacity);
+        let mut history = self.history.borrow_mut();
+        while history.len() > capacity {
+            history.pop_front();
+        }
+    }
+
+    /// Returns the kept messages, oldest first
+    ///
+    /// Release the borrow before spinning again, or storing the next message panics.
+    pub fn history(&self) -> Ref<'_, VecDeque<T>> {
+        self.history.borrow()
+    }
+
+    /// Returns the most recent kept message, see [`Subscription::history`]
+    pub fn latest(&self) -> Option<Ref<'_, T>> {
+        let history = self.history.borrow();
+        if history.is_empty() {
+            return None;
+        }
+        Some(Ref::map(history, |history| history.back().unwrap()))
+    }
+
+    fn callback_ext(
+        &self,
+        message: Box<dyn rclrs_common::traits::Message>,
+        message_info: MessageInfo,
+    ) {
         let msg = message.downcast_ref::<T>().unwrap();
-        (&mut *self.callback.borrow_mut())(msg);
+        self.callback.borrow_mut().call(msg, &message_info);
+        if let Some(next_callback) = self.next_callback.borrow_mut().take() {
+            *self.callback.borrow_mut() = next_callback;
+        }
+
+        let capacity = self.history_capacity.get();
+        if capacity > 0 {
+            let mut history = self.history.borrow_mut();
+            if history.len() == capacity {
+                history.pop_front();
+            }
+            history.push_back(*message.downcast::<T>().unwrap());
+        }
+    }
+}
+
+impl<T> Drop for Subscription<T>
+where
+    T: rclrs_common::traits::Message,
+{
+    fn drop(&mut self) {
+        if let Some((message_handle, destroy_native_message)) = self.native_message.take() {
+            destroy_native_message(message_handle);
+        }
     }
 }
 
@@ -176,7 +642,89 @@ where
         Box::new(T::default())
     }
 
-    fn callback_fn(&self, message: Box<dyn rclrs_common::traits::Message>) {
-        self.callback_ext(message);
+    fn callback_fn(
+        &self,
+        message: Box<dyn rclrs_common::traits::Message>,
+        message_info: MessageInfo,
+    ) {
+        self.callback_ext(message, message_info);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::sim_time::ClockMessage;
+    use super::*;
+    use crate::{Context, QOS_PROFILE_DEFAULT};
+    use rclrs_common::traits::{Message, MessageDefinition};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    static NATIVE_MESSAGES_CREATED: AtomicUsize = AtomicUsize::new(0);
+
+    /// `/clock` messages that count how many native messages are created for them
+    #[derive(Default)]
+    struct CountedMessage(ClockMessage);
+
+    impl Message for CountedMessage {
+        fn get_native_message(&self) -> usize {
+            Self::static_get_native_message(self)
+        }
+
+        fn destroy_native_message(&self, message_handle: usize) {
+            Self::static_destroy_native_message(message_handle);
+        }
+
+        fn read_handle(&mut self, message_handle: usize) {
+            self.0.read_handle(message_handle);
+        }
+    }
+
+    impl MessageDefinition<CountedMessage> for CountedMessage {
+        const TYPE_NAME: &'static str = ClockMessage::TYPE_NAME;
+
+        fn get_type_support() -> usize {
+            ClockMessage::get_type_support()
+        }
+
+        fn static_get_native_message(message: &CountedMessage) -> usize {
+            NATIVE_MESSAGES_CREATED.fetch_add(1, Ordering::SeqCst);
+            ClockMessage::static_get_native_message(&message.0)
+        }
+
+        fn static_destroy_native_message(message_handle: usize) {
+            ClockMessage::static_destroy_native_message(message_handle);
+        }
+    }
+
+    #[test]
+    fn take_into_creates_one_native_message() -> RclResult {
+        let context = Context::default();
+        let node = context.create_node("take_into_test")?;
+        let topic = "take_into_test";
+        let publisher = node.create_publisher::<CountedMessage>(topic, QOS_PROFILE_DEFAULT)?;
+        let subscription =
+            node.create_polling_subscription::<CountedMessage>(topic, QOS_PROFILE_DEFAULT)?;
+        assert!(publisher.wait_for_subscription(Duration::from_secs(5))?);
+
+        for _ in 0..3 {
+            publisher.publish(&CountedMessage::default()).unwrap();
+        }
+        let created_before = NATIVE_MESSAGES_CREATED.load(Ordering::SeqCst);
+
+        let mut message = CountedMessage::default();
+        let mut taken = 0;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while taken < 3 && Instant::now() < deadline {
+            if subscription.take_into(&mut message)? {
+                taken += 1;
+            }
+        }
+        assert_eq!(taken, 3);
+        assert_eq!(
+            NATIVE_MESSAGES_CREATED.load(Ordering::SeqCst),
+            created_before + 1
+        );
+        Ok(())
     }
 }
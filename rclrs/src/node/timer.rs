@@ -0,0 +1,215 @@
+use crate::error::{RclResult, ToRclResult};
+use crate::{Clock, ClockType, ContextHandle, Handle, Node};
+use rcl_sys::*;
+use std::cell::{Ref, RefCell, RefMut};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Calls a callback periodically while its node is spun
+pub struct Timer {
+    handle: RefCell<rcl_timer_t>,
+    // rcl keeps pointers to the clock and the context, so both have to outlive the timer
+    clock: Rc<Clock>,
+    _context: Rc<ContextHandle>,
+    callback: RefCell<Box<dyn FnMut()>>,
+}
+
+impl Timer {
+    /// Creates the timer in its final place, as rcl registers the address of the handle with
+    /// ROS time clocks to notify it of time jumps
+    pub(crate) fn new<F>(
+        node: &Node,
+        period: Duration,
+        clock: Rc<Clock>,
+        callback: F,
+    ) -> RclResult<Rc<Self>>
+    where
+        F: FnMut() + 'static,
+    {
+        node.ensure_valid()?;
+        // Finalizing a zero-initialized timer does nothing, so it can be dropped if init fails
+        let timer = Rc::new(Self {
+            handle: RefCell::new(unsafe { rcl_get_zero_initialized_timer() }),
+            clock,
+            _context: node.context.clone(),
+            callback: RefCell::new(Box::new(callback)),
+        });
+
+        {
+            let timer_handle = &mut *timer.get_mut();
+            let clock_handle = &mut *timer.clock.get_mut();
+            let context_handle = &mut *node.context.get_mut();
+            unsafe {
+                // The callback is called from Rust instead, see `Timer::call`
+                rcl_timer_init(
+                    timer_handle as *mut _,
+                    clock_handle as *mut _,
+                    context_handle as *mut _,
+                    period.as_nanos() as i64,
+                    None,
+                    node.allocator.rcl_allocator(),
+                )
+                .ok()?;
+            }
+        }
+
+        Ok(timer)
+    }
+
+    pub fn clock_type(&self) -> ClockType {
+        self.clock.clock_type()
+    }
+
+    pub fn period(&self) -> RclResult<Duration> {
+        let handle = &*self.get();
+        let mut period: i64 = 0;
+        unsafe {
+            rcl_timer_get_period(handle as *const _, &mut period as *mut _).ok()?;
+        }
+        Ok(Duration::from_nanos(period as u64))
+    }
+
+    /// Changes the period of the timer, returns the previous period
+    ///
+    /// rcl would only apply the new period after the next call, so the current period starts
+    /// over instead: the timer next fires one new period from now. Shortening the period
+    /// therefore never makes it fire right away, and a canceled timer stays canceled.
+    pub fn set_period(&self, period: Duration) -> RclResult<Duration> {
+        let mut old_period: i64 = 0;
+        {
+            let handle = &*self.get();
+            unsafe {
+                rcl_timer_exchange_period(
+                    handle as *const _,
+                    period.as_nanos() as i64,
+                    &mut old_period as *mut _,
+                )
+                .ok()?;
+            }
+        }
+        if !self.is_canceled()? {
+            self.reset()?;
+        }
+        Ok(Duration::from_nanos(old_period as u64))
+    }
+
+    /// Stops the timer from firing, until [`Timer::reset`] is called
+    pub fn cancel(&self) -> RclResult {
+        let handle = &mut *self.get_mut();
+        unsafe { rcl_timer_cancel(handle as *mut _).ok() }
+    }
+
+    /// Starts the current period over, which also resumes a canceled timer
+    pub fn reset(&self) -> RclResult {
+        let handle = &mut *self.get_mut();
+        unsafe { rcl_timer_reset(handle as *mut _).ok() }
+    }
+
+    pub fn is_canceled(&self) -> RclResult<bool> {
+        let handle = &*self.get();
+        let mut is_canceled = false;
+        unsafe {
+            rcl_timer_is_canceled(handle as *const _, &mut is_canceled as *mut _).ok()?;
+        }
+        Ok(is_canceled)
+    }
+
+    /// Returns how long until the timer fires next, or `None` if it is already overdue
+    ///
+    /// Canceled timers report the time they would fire at if they weren't canceled.
+    pub fn time_until_next_call(&self) -> RclResult<Option<Duration>> {
+        let handle = &*self.get();
+        let mut time_until_next_call: i64 = 0;
+        let ret = unsafe {
+            rcl_timer_get_time_until_next_call(
+                handle as *const _,
+                &mut time_until_next_call as *mut _,
+            )
+        };
+        // rcl still reports the time for canceled timers, but with a distinct return code
+        if ret as u32 != RCL_RET_TIMER_CANCELED {
+            ret.ok()?;
+        }
+        if time_until_next_call < 0 {
+            Ok(None)
+        } else {
+            Ok(Some(Duration::from_nanos(time_until_next_call as u64)))
+        }
+    }
+
+    /// Runs the callback if the timer is due
+    pub(crate) fn call(&self) -> RclResult {
+        {
+            let handle = &mut *self.get_mut();
+            let mut is_ready = false;
+            unsafe {
+                rcl_timer_is_ready(handle as *const _, &mut is_ready as *mut _).ok()?;
+                if !is_ready {
+                    return Ok(());
+                }
+                // Marks the start of the next period
+                rcl_timer_call(handle as *mut _).ok()?;
+            }
+        }
+        // Not borrowing the handle, so the callback can use the timer
+        (*self.callback.borrow_mut())();
+        Ok(())
+    }
+}
+
+impl<'a> Handle<rcl_timer_t> for &'a Timer {
+    type DerefT = Ref<'a, rcl_timer_t>;
+    type DerefMutT = RefMut<'a, rcl_timer_t>;
+
+    fn get(self) -> Self::DerefT {
+        self.handle.borrow()
+    }
+
+    fn get_mut(self) -> Self::DerefMutT {
+        self.handle.borrow_mut()
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let handle = &mut *self.get_mut();
+        unsafe {
+            rcl_timer_fini(handle as *mut _).unwrap_in_drop("rcl_timer_fini");
+        }
+    }
+}
+
+impl Node {
+    /// Creates a timer on the steady clock, so it fires at the same rate when the system time
+    /// is adjusted, e.g. by NTP
+    pub fn create_timer<F>(&self, period: Duration, callback: F) -> RclResult<Rc<Timer>>
+    where
+        F: FnMut() + 'static,
+    {
+        self.create_timer_with_clock(period, ClockType::SteadyTime, callback)
+    }
+
+    /// Creates a timer on a clock of the given type
+    ///
+    /// A [`ClockType::RosTime`] timer uses the node's clock, so it follows simulated time
+    /// when [`Node::use_sim_time`] is enabled. [`ClockType::SteadyTime`] timers share one clock
+    /// per context, instead of each creating their own.
+    pub fn create_timer_with_clock<F>(
+        &self,
+        period: Duration,
+        clock_type: ClockType,
+        callback: F,
+    ) -> RclResult<Rc<Timer>>
+    where
+        F: FnMut() + 'static,
+    {
+        let clock = match clock_type {
+            ClockType::RosTime => self.clock.clone(),
+            ClockType::SteadyTime => self.context.steady_clock(self.allocator.clone())?,
+            _ => Rc::new(Clock::new(clock_type, self.allocator.clone())?),
+        };
+        let timer = Timer::new(self, period, clock, callback)?;
+        self.timers.borrow_mut().push(Rc::downgrade(&timer));
+        Ok(timer)
+    }
+}
@@ -0,0 +1,23 @@
+//! Commonly used types, for a single glob import
+//!
+//! ```ignore
+//! use rclrs::prelude::*;
+//!
+//! let context = Context::default();
+//! let node = Node::builder("my_node", &context).build()?;
+//! spin(&node)?;
+//! ```
+
+pub use crate::clock::{Clock, ClockType, Time};
+pub use crate::context::{Context, InitOptions};
+pub use crate::error::{RclError, RclResult};
+pub use crate::node::{
+    DynamicPublisher, DynamicSubscription, GuardCondition, LifecycleNode, MessageInfo, Node,
+    NodeBuilder, Publisher, Subscription, SubscriptionOptions, Timer,
+};
+pub use crate::qos::{
+    QoSDurabilityPolicy, QoSHistoryPolicy, QoSLivelinessPolicy, QoSProfile, QoSReliabilityPolicy,
+    QOS_PROFILE_DEFAULT, QOS_PROFILE_SENSOR_DATA, QOS_PROFILE_SYSTEM_DEFAULT,
+};
+pub use crate::rate::Rate;
+pub use crate::{spin, spin_n, spin_nodes, spin_once, spin_once_nodes, spin_until};
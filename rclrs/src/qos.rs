@@ -1,4 +1,6 @@
+use crate::error::{RclError, RclResult};
 use rcl_sys::*;
+use std::time::Duration;
 
 pub enum QoSReliabilityPolicy {
     SystemDefault = 0,
@@ -9,6 +11,12 @@ pub enum QoSReliabilityPolicy {
 pub enum QoSHistoryPolicy {
     SystemDefault = 0,
     KeepLast = 1,
+    /// Keeps every message until it is delivered, limited only by the middleware's resource
+    /// limits
+    ///
+    /// With a reliable publisher, a subscription that stops taking messages makes the queues
+    /// grow without bound. rmw offers no way to set resource limits, so they can only be set
+    /// in the middleware's own configuration, e.g. an XML profile.
     KeepAll = 2,
 }
 
@@ -18,12 +26,172 @@ pub enum QoSDurabilityPolicy {
     Volatile = 2,
 }
 
+pub enum QoSLivelinessPolicy {
+    SystemDefault = 0,
+    /// The middleware asserts liveliness whenever any publisher of the node publishes
+    Automatic = 1,
+    /// Liveliness is only asserted by publishing or by [`crate::Publisher::assert_liveliness`]
+    ManualByTopic = 3,
+}
+
 pub struct QoSProfile {
     pub history: QoSHistoryPolicy,
     pub depth: isize,
     pub reliability: QoSReliabilityPolicy,
     pub durability: QoSDurabilityPolicy,
     pub avoid_ros_namespace_conventions: bool,
+    pub liveliness: QoSLivelinessPolicy,
+    /// Time after which a publisher is considered no longer alive, zero for the default
+    pub liveliness_lease_duration: Duration,
+    /// Longest expected time between messages, zero for the default
+    pub deadline: Duration,
+}
+
+/// Setters to start from a preset and override parts of it
+///
+/// ```ignore
+/// let qos = QOS_PROFILE_SENSOR_DATA.reliable().keep_last(1);
+/// ```
+impl QoSProfile {
+    pub fn reliable(mut self) -> Self {
+        self.reliability = QoSReliabilityPolicy::Reliable;
+        self
+    }
+
+    pub fn best_effort(mut self) -> Self {
+        self.reliability = QoSReliabilityPolicy::BestEffort;
+        self
+    }
+
+    pub fn keep_last(mut self, depth: isize) -> Self {
+        self.history = QoSHistoryPolicy::KeepLast;
+        self.depth = depth;
+        self
+    }
+
+    pub fn keep_all(mut self) -> Self {
+        self.history = QoSHistoryPolicy::KeepAll;
+        self
+    }
+
+    pub fn transient_local(mut self) -> Self {
+        self.durability = QoSDurabilityPolicy::TransientLocal;
+        self
+    }
+
+    pub fn volatile(mut self) -> Self {
+        self.durability = QoSDurabilityPolicy::Volatile;
+        self
+    }
+
+    pub fn liveliness(mut self, liveliness: QoSLivelinessPolicy) -> Self {
+        self.liveliness = liveliness;
+        self
+    }
+
+    pub fn liveliness_lease_duration(mut self, duration: Duration) -> Self {
+        self.liveliness_lease_duration = duration;
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+}
+
+/// Outcome of [`QoSProfile::is_compatible_with`], with the reasons for anything but `Ok`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QoSCompatibility {
+    Ok,
+    /// Whether they match depends on policies left to the middleware's default
+    Warning(String),
+    /// The subscription won't receive messages from the publisher
+    Error(String),
+}
+
+impl QoSProfile {
+    /// Checks the profile before it's handed to rmw, so that keeping the last zero or a
+    /// negative number of messages is refused with [`RclError::InvalidArgument`]
+    ///
+    /// The depth is ignored with the other history policies.
+    pub fn validate(&self) -> RclResult {
+        match self.history {
+            QoSHistoryPolicy::KeepLast if self.depth <= 0 => Err(RclError::InvalidArgument),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks whether a subscription with this profile is matched with a publisher offering
+    /// `publisher`, following the DDS request-offer rules
+    ///
+    /// rmw can't check this on this distro, so the rules are applied here: the publisher has to
+    /// offer at least the reliability, durability, deadline and liveliness the subscription
+    /// requests. The profiles of existing publishers are reported by
+    /// [`crate::Node::get_publishers_info_by_topic`].
+    pub fn is_compatible_with(&self, publisher: &QoSProfile) -> QoSCompatibility {
+        use QoSDurabilityPolicy as Durability;
+        use QoSLivelinessPolicy as Liveliness;
+        use QoSReliabilityPolicy as Reliability;
+
+        let mut errors = vec![];
+        let mut warnings = vec![];
+
+        match (&publisher.reliability, &self.reliability) {
+            (Reliability::BestEffort, Reliability::Reliable) => {
+                errors.push("best effort publisher and reliable subscription")
+            }
+            (Reliability::SystemDefault, Reliability::Reliable)
+            | (Reliability::BestEffort, Reliability::SystemDefault) => {
+                warnings.push("reliability depends on the middleware's default")
+            }
+            _ => {}
+        }
+        match (&publisher.durability, &self.durability) {
+            (Durability::Volatile, Durability::TransientLocal) => {
+                errors.push("volatile publisher and transient local subscription")
+            }
+            (Durability::SystemDefault, Durability::TransientLocal)
+            | (Durability::Volatile, Durability::SystemDefault) => {
+                warnings.push("durability depends on the middleware's default")
+            }
+            _ => {}
+        }
+        match (&publisher.liveliness, &self.liveliness) {
+            (Liveliness::Automatic, Liveliness::ManualByTopic) => {
+                errors.push("automatic liveliness publisher and manual by topic subscription")
+            }
+            (Liveliness::SystemDefault, Liveliness::ManualByTopic)
+            | (Liveliness::Automatic, Liveliness::SystemDefault) => {
+                warnings.push("liveliness depends on the middleware's default")
+            }
+            _ => {}
+        }
+        // Zero stands for the default, which is infinite
+        if offers_less(publisher.deadline, self.deadline) {
+            errors.push("publisher deadline longer than the subscription's");
+        }
+        if offers_less(
+            publisher.liveliness_lease_duration,
+            self.liveliness_lease_duration,
+        ) {
+            errors.push("publisher liveliness lease duration longer than the subscription's");
+        }
+
+        if !errors.is_empty() {
+            QoSCompatibility::Error(errors.join("; "))
+        } else if !warnings.is_empty() {
+            QoSCompatibility::Warning(warnings.join("; "))
+        } else {
+            QoSCompatibility::Ok
+        }
+    }
+}
+
+/// Whether an offered period, zero for infinite, is longer than the requested one
+fn offers_less(offered: Duration, requested: Duration) -> bool {
+    requested != Duration::from_secs(0)
+        && (offered == Duration::from_secs(0) || offered > requested)
 }
 
 pub const QOS_PROFILE_SENSOR_DATA: QoSProfile = QoSProfile {
@@ -32,6 +200,9 @@ pub const QOS_PROFILE_SENSOR_DATA: QoSProfile = QoSProfile {
     reliability: QoSReliabilityPolicy::BestEffort,
     durability: QoSDurabilityPolicy::Volatile,
     avoid_ros_namespace_conventions: false,
+    liveliness: QoSLivelinessPolicy::SystemDefault,
+    liveliness_lease_duration: Duration::from_secs(0),
+    deadline: Duration::from_secs(0),
 };
 
 pub const QOS_PROFILE_PARAMETERS: QoSProfile = QoSProfile {
@@ -40,6 +211,9 @@ pub const QOS_PROFILE_PARAMETERS: QoSProfile = QoSProfile {
     reliability: QoSReliabilityPolicy::Reliable,
     durability: QoSDurabilityPolicy::Volatile,
     avoid_ros_namespace_conventions: false,
+    liveliness: QoSLivelinessPolicy::SystemDefault,
+    liveliness_lease_duration: Duration::from_secs(0),
+    deadline: Duration::from_secs(0),
 };
 
 pub const QOS_PROFILE_DEFAULT: QoSProfile = QoSProfile {
@@ -48,6 +222,9 @@ pub const QOS_PROFILE_DEFAULT: QoSProfile = QoSProfile {
     reliability: QoSReliabilityPolicy::Reliable,
     durability: QoSDurabilityPolicy::Volatile,
     avoid_ros_namespace_conventions: false,
+    liveliness: QoSLivelinessPolicy::SystemDefault,
+    liveliness_lease_duration: Duration::from_secs(0),
+    deadline: Duration::from_secs(0),
 };
 
 pub const QOS_PROFILE_SERVICES_DEFAULT: QoSProfile = QoSProfile {
@@ -56,6 +233,9 @@ pub const QOS_PROFILE_SERVICES_DEFAULT: QoSProfile = QoSProfile {
     reliability: QoSReliabilityPolicy::Reliable,
     durability: QoSDurabilityPolicy::Volatile,
     avoid_ros_namespace_conventions: false,
+    liveliness: QoSLivelinessPolicy::SystemDefault,
+    liveliness_lease_duration: Duration::from_secs(0),
+    deadline: Duration::from_secs(0),
 };
 
 pub const QOS_PROFILE_PARAMETER_EVENTS: QoSProfile = QoSProfile {
@@ -64,6 +244,9 @@ pub const QOS_PROFILE_PARAMETER_EVENTS: QoSProfile = QoSProfile {
     reliability: QoSReliabilityPolicy::Reliable,
     durability: QoSDurabilityPolicy::Volatile,
     avoid_ros_namespace_conventions: false,
+    liveliness: QoSLivelinessPolicy::SystemDefault,
+    liveliness_lease_duration: Duration::from_secs(0),
+    deadline: Duration::from_secs(0),
 };
 
 pub const SYSTEM_DEFAULT: isize = 0;
@@ -74,6 +257,9 @@ pub const QOS_PROFILE_SYSTEM_DEFAULT: QoSProfile = QoSProfile {
     reliability: QoSReliabilityPolicy::SystemDefault,
     durability: QoSDurabilityPolicy::SystemDefault,
     avoid_ros_namespace_conventions: false,
+    liveliness: QoSLivelinessPolicy::SystemDefault,
+    liveliness_lease_duration: Duration::from_secs(0),
+    deadline: Duration::from_secs(0),
 };
 
 impl From<QoSProfile> for rmw_qos_profile_t {
@@ -84,14 +270,39 @@ impl From<QoSProfile> for rmw_qos_profile_t {
             reliability: qos.reliability.into(),
             durability: qos.durability.into(),
             avoid_ros_namespace_conventions: qos.avoid_ros_namespace_conventions,
-            deadline: rmw_time_t { sec: 0, nsec: 0 },
-            lifespan: rmw_time_t { sec: 0, nsec: 0 },
-            liveliness_lease_duration: rmw_time_t { sec: 0, nsec: 0 },
-            liveliness: rmw_qos_liveliness_policy_t::RMW_QOS_POLICY_LIVELINESS_SYSTEM_DEFAULT,
+            deadline: duration_to_rmw_time(qos.deadline),
+            lifespan: duration_to_rmw_time(Duration::from_secs(0)),
+            liveliness_lease_duration: duration_to_rmw_time(qos.liveliness_lease_duration),
+            liveliness: qos.liveliness.into(),
         }
     }
 }
 
+/// Longest duration the rmw implementations can handle, which they also take as infinite
+///
+/// They convert durations to signed 64 bit nanoseconds internally.
+const RMW_DURATION_INFINITE: Duration = Duration::from_nanos(i64::MAX as u64);
+
+/// Converts a QoS duration for rmw, where zero means the default of the middleware
+///
+/// Durations beyond [`RMW_DURATION_INFINITE`] are clamped to it instead of overflowing.
+pub(crate) fn duration_to_rmw_time(duration: Duration) -> rmw_time_t {
+    let duration = duration.min(RMW_DURATION_INFINITE);
+    rmw_time_t {
+        sec: duration.as_secs(),
+        nsec: u64::from(duration.subsec_nanos()),
+    }
+}
+
+pub(crate) fn rmw_time_to_duration(time: &rmw_time_t) -> Duration {
+    // The nanoseconds aren't necessarily less than a second
+    Duration::from_secs(time.sec)
+        .checked_add(Duration::from_nanos(time.nsec))
+        .map_or(RMW_DURATION_INFINITE, |duration| {
+            duration.min(RMW_DURATION_INFINITE)
+        })
+}
+
 impl From<QoSHistoryPolicy> for rmw_qos_history_policy_t {
     fn from(policy: QoSHistoryPolicy) -> Self {
         match policy {
@@ -137,3 +348,225 @@ impl From<QoSDurabilityPolicy> for rmw_qos_durability_policy_t {
         }
     }
 }
+
+impl From<&rmw_qos_profile_t> for QoSProfile {
+    fn from(qos: &rmw_qos_profile_t) -> Self {
+        Self {
+            history: qos.history.into(),
+            depth: qos.depth as isize,
+            reliability: qos.reliability.into(),
+            durability: qos.durability.into(),
+            avoid_ros_namespace_conventions: qos.avoid_ros_namespace_conventions,
+            liveliness: qos.liveliness.into(),
+            liveliness_lease_duration: rmw_time_to_duration(&qos.liveliness_lease_duration),
+            deadline: rmw_time_to_duration(&qos.deadline),
+        }
+    }
+}
+
+impl From<rmw_qos_history_policy_t> for QoSHistoryPolicy {
+    fn from(policy: rmw_qos_history_policy_t) -> Self {
+        match policy {
+            rmw_qos_history_policy_t::RMW_QOS_POLICY_HISTORY_KEEP_LAST => {
+                QoSHistoryPolicy::KeepLast
+            }
+            rmw_qos_history_policy_t::RMW_QOS_POLICY_HISTORY_KEEP_ALL => QoSHistoryPolicy::KeepAll,
+            _ => QoSHistoryPolicy::SystemDefault,
+        }
+    }
+}
+
+impl From<rmw_qos_reliability_policy_t> for QoSReliabilityPolicy {
+    fn from(policy: rmw_qos_reliability_policy_t) -> Self {
+        match policy {
+            rmw_qos_reliability_policy_t::RMW_QOS_POLICY_RELIABILITY_RELIABLE => {
+                QoSReliabilityPolicy::Reliable
+            }
+            rmw_qos_reliability_policy_t::RMW_QOS_POLICY_RELIABILITY_BEST_EFFORT => {
+                QoSReliabilityPolicy::BestEffort
+            }
+            _ => QoSReliabilityPolicy::SystemDefault,
+        }
+    }
+}
+
+impl From<rmw_qos_durability_policy_t> for QoSDurabilityPolicy {
+    fn from(policy: rmw_qos_durability_policy_t) -> Self {
+        match policy {
+            rmw_qos_durability_policy_t::RMW_QOS_POLICY_DURABILITY_TRANSIENT_LOCAL => {
+                QoSDurabilityPolicy::TransientLocal
+            }
+            rmw_qos_durability_policy_t::RMW_QOS_POLICY_DURABILITY_VOLATILE => {
+                QoSDurabilityPolicy::Volatile
+            }
+            _ => QoSDurabilityPolicy::SystemDefault,
+        }
+    }
+}
+
+impl From<QoSLivelinessPolicy> for rmw_qos_liveliness_policy_t {
+    fn from(policy: QoSLivelinessPolicy) -> Self {
+        match policy {
+            QoSLivelinessPolicy::SystemDefault => {
+                rmw_qos_liveliness_policy_t::RMW_QOS_POLICY_LIVELINESS_SYSTEM_DEFAULT
+            }
+            QoSLivelinessPolicy::Automatic => {
+                rmw_qos_liveliness_policy_t::RMW_QOS_POLICY_LIVELINESS_AUTOMATIC
+            }
+            QoSLivelinessPolicy::ManualByTopic => {
+                rmw_qos_liveliness_policy_t::RMW_QOS_POLICY_LIVELINESS_MANUAL_BY_TOPIC
+            }
+        }
+    }
+}
+
+impl From<rmw_qos_liveliness_policy_t> for QoSLivelinessPolicy {
+    fn from(policy: rmw_qos_liveliness_policy_t) -> Self {
+        match policy {
+            rmw_qos_liveliness_policy_t::RMW_QOS_POLICY_LIVELINESS_AUTOMATIC => {
+                QoSLivelinessPolicy::Automatic
+            }
+            rmw_qos_liveliness_policy_t::RMW_QOS_POLICY_LIVELINESS_MANUAL_BY_TOPIC => {
+                QoSLivelinessPolicy::ManualByTopic
+            }
+            _ => QoSLivelinessPolicy::SystemDefault,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_duration_stays_the_default() {
+        let time = duration_to_rmw_time(Duration::from_secs(0));
+        assert_eq!((time.sec, time.nsec), (0, 0));
+        assert_eq!(rmw_time_to_duration(&time), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn durations_round_trip() {
+        let duration = Duration::new(3, 500_000_000);
+        let time = duration_to_rmw_time(duration);
+        assert_eq!((time.sec, time.nsec), (3, 500_000_000));
+        assert_eq!(rmw_time_to_duration(&time), duration);
+    }
+
+    #[test]
+    fn long_durations_are_clamped_to_infinite() {
+        let time = duration_to_rmw_time(Duration::new(u64::MAX, 999_999_999));
+        assert_eq!(rmw_time_to_duration(&time), RMW_DURATION_INFINITE);
+
+        let time = rmw_time_t {
+            sec: u64::MAX,
+            nsec: u64::MAX,
+        };
+        assert_eq!(rmw_time_to_duration(&time), RMW_DURATION_INFINITE);
+    }
+
+    #[test]
+    fn nanoseconds_beyond_a_second_are_carried() {
+        let time = rmw_time_t {
+            sec: 1,
+            nsec: 1_500_000_000,
+        };
+        assert_eq!(rmw_time_to_duration(&time), Duration::new(2, 500_000_000));
+    }
+
+    #[test]
+    fn setters_override_the_preset() {
+        let qos = QOS_PROFILE_SENSOR_DATA
+            .reliable()
+            .keep_last(1)
+            .transient_local()
+            .liveliness(QoSLivelinessPolicy::ManualByTopic)
+            .liveliness_lease_duration(Duration::from_secs(2))
+            .deadline(Duration::from_millis(100));
+        assert!(matches!(qos.reliability, QoSReliabilityPolicy::Reliable));
+        assert!(matches!(qos.history, QoSHistoryPolicy::KeepLast));
+        assert_eq!(qos.depth, 1);
+        assert!(matches!(
+            qos.durability,
+            QoSDurabilityPolicy::TransientLocal
+        ));
+        assert!(matches!(qos.liveliness, QoSLivelinessPolicy::ManualByTopic));
+        assert_eq!(qos.liveliness_lease_duration, Duration::from_secs(2));
+        assert_eq!(qos.deadline, Duration::from_millis(100));
+
+        let qos = QOS_PROFILE_DEFAULT.best_effort().keep_all().volatile();
+        assert!(matches!(qos.reliability, QoSReliabilityPolicy::BestEffort));
+        assert!(matches!(qos.history, QoSHistoryPolicy::KeepAll));
+        assert!(matches!(qos.durability, QoSDurabilityPolicy::Volatile));
+    }
+
+    #[test]
+    fn matching_profiles_are_compatible() {
+        let qos = QOS_PROFILE_DEFAULT;
+        assert_eq!(
+            qos.is_compatible_with(&QOS_PROFILE_DEFAULT),
+            QoSCompatibility::Ok
+        );
+        // A publisher may offer more than requested
+        let subscription = QOS_PROFILE_DEFAULT.best_effort().volatile();
+        let publisher = QOS_PROFILE_DEFAULT.reliable().transient_local();
+        assert_eq!(
+            subscription.is_compatible_with(&publisher),
+            QoSCompatibility::Ok
+        );
+    }
+
+    #[test]
+    fn publishers_offering_less_are_incompatible() {
+        let subscription = QOS_PROFILE_DEFAULT.reliable();
+        let publisher = QOS_PROFILE_DEFAULT.best_effort();
+        assert!(matches!(
+            subscription.is_compatible_with(&publisher),
+            QoSCompatibility::Error(_)
+        ));
+
+        let subscription = QOS_PROFILE_DEFAULT.transient_local();
+        let publisher = QOS_PROFILE_DEFAULT.volatile();
+        assert!(matches!(
+            subscription.is_compatible_with(&publisher),
+            QoSCompatibility::Error(_)
+        ));
+
+        let subscription = QOS_PROFILE_DEFAULT.deadline(Duration::from_millis(100));
+        let publisher = QOS_PROFILE_DEFAULT.deadline(Duration::from_secs(1));
+        assert!(matches!(
+            subscription.is_compatible_with(&publisher),
+            QoSCompatibility::Error(_)
+        ));
+        // No deadline is an infinite one
+        assert!(matches!(
+            subscription.is_compatible_with(&QOS_PROFILE_DEFAULT),
+            QoSCompatibility::Error(_)
+        ));
+    }
+
+    #[test]
+    fn system_defaults_only_warn() {
+        let subscription = QOS_PROFILE_DEFAULT.reliable();
+        assert!(matches!(
+            subscription.is_compatible_with(&QOS_PROFILE_SYSTEM_DEFAULT),
+            QoSCompatibility::Warning(_)
+        ));
+    }
+
+    #[test]
+    fn keep_last_needs_a_positive_depth() {
+        assert!(matches!(
+            QOS_PROFILE_DEFAULT.keep_last(0).validate(),
+            Err(RclError::InvalidArgument)
+        ));
+        assert!(matches!(
+            QOS_PROFILE_DEFAULT.keep_last(-1).validate(),
+            Err(RclError::InvalidArgument)
+        ));
+        assert!(QOS_PROFILE_DEFAULT.keep_last(1).validate().is_ok());
+        // The depth only matters when keeping the last messages
+        assert!(QOS_PROFILE_SYSTEM_DEFAULT.validate().is_ok());
+        assert!(QOS_PROFILE_DEFAULT.keep_all().validate().is_ok());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::error::{RclError, RclResult};
+use crate::{Clock, ClockType};
+use std::convert::TryFrom;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+/// Longest stretch a [`Rate`] on a ROS time clock sleeps before checking the clock again
+///
+/// Simulated time doesn't advance with wall time, so the remaining time can't be slept in one go.
+const ROS_TIME_POLL_PERIOD: Duration = Duration::from_millis(1);
+
+/// Keeps a loop running at a fixed frequency
+///
+/// ```ignore
+/// let mut rate = rclrs::Rate::new(10.0, node.get_clock())?;
+/// while context.ok() {
+///     // ...
+///     rate.sleep()?;
+/// }
+/// ```
+pub struct Rate {
+    period: i64,
+    clock: Rc<Clock>,
+    /// Time at which the current period ends, in nanoseconds
+    next: i64,
+}
+
+impl Rate {
+    /// Creates a rate running at `frequency` Hz, measured with `clock`
+    ///
+    /// The first period starts right away. A frequency that isn't positive and finite, or is so
+    /// low that the period doesn't fit in a `Duration`, is refused with
+    /// [`RclError::InvalidArgument`].
+    pub fn new(frequency: f64, clock: Rc<Clock>) -> RclResult<Self> {
+        if !(frequency.is_finite() && frequency > 0.0) {
+            return Err(RclError::InvalidArgument);
+        }
+        let period = 1.0 / frequency;
+        if !period.is_finite() || period >= u64::MAX as f64 {
+            return Err(RclError::InvalidArgument);
+        }
+        Self::from_period(Duration::from_secs_f64(period), clock)
+    }
+
+    /// Creates a rate with a period of `period`, measured with `clock`
+    ///
+    /// Like [`Rate::new`], the first period starts right away. A period longer than `i64::MAX`
+    /// nanoseconds, which ROS time can't represent, is refused with
+    /// [`RclError::InvalidArgument`].
+    pub fn from_period(period: Duration, clock: Rc<Clock>) -> RclResult<Self> {
+        let period = i64::try_from(period.as_nanos()).map_err(|_| RclError::InvalidArgument)?;
+        let next = clock
+            .now()?
+            .nanoseconds
+            .checked_add(period)
+            .ok_or(RclError::InvalidArgument)?;
+        Ok(Self {
+            period,
+            clock,
+            next,
+        })
+    }
+
+    /// Returns the time between the ends of two periods
+    pub fn period(&self) -> Duration {
+        Duration::from_nanos(self.period as u64)
+    }
+
+    /// Blocks until the end of the current period and starts the next one
+    ///
+    /// Periods are back to back, so time spent in the loop body counts towards the period and
+    /// the average frequency is kept. If the loop body overran the period, this returns right
+    /// away; if it overran by more than a whole period, the next period starts now instead of
+    /// trying to catch up.
+    pub fn sleep(&mut self) -> RclResult {
+        loop {
+            let now = self.clock.now()?.nanoseconds;
+            let remaining = self.next - now;
+            if remaining <= 0 {
+                self.next += self.period;
+                if self.next <= now {
+                    self.next = now + self.period;
+                }
+                return Ok(());
+            }
+
+            let remaining = Duration::from_nanos(remaining as u64);
+            match self.clock.clock_type() {
+                ClockType::RosTime => thread::sleep(remaining.min(ROS_TIME_POLL_PERIOD)),
+                _ => thread::sleep(remaining),
+            }
+        }
+    }
+
+    /// Starts the current period over, e.g. after the loop was paused
+    pub fn reset(&mut self) -> RclResult {
+        self.next = self.clock.now()?.nanoseconds + self.period;
+        Ok(())
+    }
+}
@@ -0,0 +1,321 @@
+use crate::error::RclError;
+use crate::qos::QOS_PROFILE_DEFAULT;
+use crate::{Clock, DynamicSubscription, Node, Timer};
+use failure::Fail;
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Identifies a file written by [`Recorder`], followed by the format version
+const RECORDING_MAGIC: &[u8; 8] = b"RCLRSREC";
+const RECORDING_VERSION: u32 = 1;
+
+/// How often a recording [`Recorder`] takes the messages its subscriptions received
+const RECORDER_POLL_PERIOD: Duration = Duration::from_millis(10);
+
+/// Error returned when recording, or reading back a recording, fails
+#[derive(Debug, Fail)]
+pub enum RecordError {
+    #[fail(display = "recording file error: {}", _0)]
+    Io(#[cause] std::io::Error),
+    #[fail(display = "taking a message failed: {}", _0)]
+    Rcl(#[cause] RclError),
+}
+
+impl From<std::io::Error> for RecordError {
+    fn from(error: std::io::Error) -> Self {
+        RecordError::Io(error)
+    }
+}
+
+impl From<RclError> for RecordError {
+    fn from(error: RclError) -> Self {
+        RecordError::Rcl(error)
+    }
+}
+
+/// A message as stored in a recording
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedMessage {
+    pub topic: String,
+    pub type_name: String,
+    /// When the message was taken, on the node's clock
+    pub timestamp_nanoseconds: i64,
+    /// The message as serialized by the middleware
+    pub data: Vec<u8>,
+}
+
+/// Records the serialized messages on a set of topics into a file
+///
+/// The file is a simple length-prefixed format, not a rosbag2 database; read it back with
+/// [`read_recording`]. Messages are taken by a timer on the node, so they are only recorded
+/// while the node is spun. Messages received while not recording are dropped.
+///
+/// ```ignore
+/// let mut recorder = Recorder::new(&node, &[("/chatter", "std_msgs/msg/String")])?;
+/// recorder.start("chatter.rec")?;
+/// spin_n(&node, 100, Duration::from_millis(10))?;
+/// recorder.stop()?;
+/// ```
+pub struct Recorder {
+    state: Rc<RefCell<RecorderState>>,
+    timer: Rc<Timer>,
+}
+
+struct RecorderState {
+    subscriptions: Vec<(String, DynamicSubscription)>,
+    clock: Rc<Clock>,
+    writer: Option<BufWriter<File>>,
+    // Timer callbacks can't return errors, so the first one is kept for `stop`
+    error: Option<RecordError>,
+}
+
+impl Recorder {
+    /// Subscribes to the given topics, each with its message type, e.g. `"std_msgs/msg/String"`
+    pub fn new(node: &Node, topics: &[(&str, &str)]) -> Result<Self, RecordError> {
+        let subscriptions = topics
+            .iter()
+            .map(|&(topic, type_name)| {
+                let subscription =
+                    DynamicSubscription::new(node, topic, type_name, QOS_PROFILE_DEFAULT)?;
+                Ok((topic.to_owned(), subscription))
+            })
+            .collect::<Result<Vec<_>, RclError>>()?;
+
+        let state = Rc::new(RefCell::new(RecorderState {
+            subscriptions,
+            clock: node.get_clock(),
+            writer: None,
+            error: None,
+        }));
+
+        let timer_state = Rc::clone(&state);
+        let timer = node.create_timer(RECORDER_POLL_PERIOD, move || {
+            let mut state = timer_state.borrow_mut();
+            if state.error.is_none() {
+                if let Err(error) = state.record_received() {
+                    state.error = Some(error);
+                }
+            }
+        })?;
+        timer.cancel()?;
+
+        Ok(Self { state, timer })
+    }
+
+    /// Starts recording into a new file at `path`, replacing any existing one
+    pub fn start<P: AsRef<Path>>(&mut self, path: P) -> Result<(), RecordError> {
+        self.stop()?;
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_header(&mut writer)?;
+
+        {
+            let mut state = self.state.borrow_mut();
+            for (_, subscription) in &state.subscriptions {
+                while subscription.take_serialized()?.is_some() {}
+            }
+            state.writer = Some(writer);
+        }
+        self.timer.reset()?;
+        Ok(())
+    }
+
+    /// Records what is left to take, and closes the file
+    ///
+    /// Returns the first error that occurred while recording, if any. Does nothing if not
+    /// recording.
+    pub fn stop(&mut self) -> Result<(), RecordError> {
+        self.timer.cancel()?;
+
+        let mut state = self.state.borrow_mut();
+        if state.writer.is_none() {
+            return Ok(());
+        }
+        let result = state.record_received();
+        let writer = state.writer.take();
+        if let Some(error) = state.error.take() {
+            return Err(error);
+        }
+        result?;
+        writer.unwrap().flush()?;
+        Ok(())
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.state.borrow().writer.is_some()
+    }
+}
+
+impl RecorderState {
+    fn record_received(&mut self) -> Result<(), RecordError> {
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => return Ok(()),
+        };
+        for (topic, subscription) in &self.subscriptions {
+            while let Some(data) = subscription.take_serialized()? {
+                let timestamp = self.clock.now()?.nanoseconds;
+                write_record(writer, timestamp, topic, subscription.type_name(), &data)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads back all messages of a file written by [`Recorder`], in the order they were recorded
+pub fn read_recording<P: AsRef<Path>>(path: P) -> Result<Vec<RecordedMessage>, RecordError> {
+    Ok(read_records(&mut BufReader::new(File::open(path)?))?)
+}
+
+fn write_header<W: Write>(writer: &mut W) -> std::io::Result<()> {
+    writer.write_all(RECORDING_MAGIC)?;
+    writer.write_all(&RECORDING_VERSION.to_le_bytes())
+}
+
+fn write_record<W: Write>(
+    writer: &mut W,
+    timestamp_nanoseconds: i64,
+    topic: &str,
+    type_name: &str,
+    data: &[u8],
+) -> std::io::Result<()> {
+    writer.write_all(&timestamp_nanoseconds.to_le_bytes())?;
+    write_bytes(writer, topic.as_bytes())?;
+    write_bytes(writer, type_name.as_bytes())?;
+    write_bytes(writer, data)
+}
+
+fn read_records<R: Read>(reader: &mut R) -> std::io::Result<Vec<RecordedMessage>> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+    if &magic != RECORDING_MAGIC || u32::from_le_bytes(version) != RECORDING_VERSION {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "not an rclrs recording",
+        ));
+    }
+
+    let mut messages = vec![];
+    loop {
+        let mut timestamp = [0; 8];
+        match reader.read_exact(&mut timestamp) {
+            Ok(()) => {}
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(error),
+        }
+        let topic = read_string(reader)?;
+        let type_name = read_string(reader)?;
+        let data = read_bytes(reader)?;
+        messages.push(RecordedMessage {
+            topic,
+            type_name,
+            timestamp_nanoseconds: i64::from_le_bytes(timestamp),
+            data,
+        });
+    }
+
+    Ok(messages)
+}
+
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> std::io::Result<()> {
+    let length = u32::try_from(bytes.len())
+        .map_err(|_| std::io::Error::new(ErrorKind::InvalidInput, "record too long"))?;
+    writer.write_all(&length.to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> std::io::Result<Vec<u8>> {
+    let mut length = [0; 4];
+    reader.read_exact(&mut length)?;
+    let length = u64::from(u32::from_le_bytes(length));
+    // The length isn't trusted to allocate up front, a corrupt one would make that huge
+    let mut bytes = vec![];
+    reader.take(length).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != length {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "recording ends in the middle of a record",
+        ));
+    }
+    Ok(bytes)
+}
+
+fn read_string<R: Read>(reader: &mut R) -> std::io::Result<String> {
+    String::from_utf8(read_bytes(reader)?)
+        .map_err(|error| std::io::Error::new(ErrorKind::InvalidData, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn recording(records: &[(i64, &str, &str, &[u8])]) -> Vec<u8> {
+        let mut buffer = vec![];
+        write_header(&mut buffer).unwrap();
+        for &(timestamp, topic, type_name, data) in records {
+            write_record(&mut buffer, timestamp, topic, type_name, data).unwrap();
+        }
+        buffer
+    }
+
+    #[test]
+    fn records_are_read_back_in_order() {
+        let buffer = recording(&[
+            (1, "/chatter", "std_msgs/msg/String", &[1, 2, 3]),
+            (-2, "/empty", "std_msgs/msg/Empty", &[]),
+        ]);
+
+        let messages = read_records(&mut Cursor::new(buffer)).unwrap();
+        assert_eq!(
+            messages,
+            vec![
+                RecordedMessage {
+                    topic: "/chatter".to_owned(),
+                    type_name: "std_msgs/msg/String".to_owned(),
+                    timestamp_nanoseconds: 1,
+                    data: vec![1, 2, 3],
+                },
+                RecordedMessage {
+                    topic: "/empty".to_owned(),
+                    type_name: "std_msgs/msg/Empty".to_owned(),
+                    timestamp_nanoseconds: -2,
+                    data: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn other_files_are_refused() {
+        let error = read_records(&mut Cursor::new(b"NOTARECORDING".to_vec())).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_records_are_refused() {
+        let mut buffer = recording(&[(1, "/chatter", "std_msgs/msg/String", &[1, 2, 3])]);
+        buffer.pop();
+
+        let error = read_records(&mut Cursor::new(buffer)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn corrupt_lengths_are_refused() {
+        let mut buffer = recording(&[]);
+        buffer.extend_from_slice(&1_i64.to_le_bytes());
+        buffer.extend_from_slice(&u32::MAX.to_le_bytes());
+        buffer.extend_from_slice(b"/chatter");
+
+        let error = read_records(&mut Cursor::new(buffer)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::error::{MessageValidationError, RclError, RclResult, ToRclResult};
+use crate::{Allocator, DefaultAllocator};
+use failure::Fail;
+use rcl_sys::*;
+use rclrs_common::traits::MessageDefinition;
+use std::os::raw::c_void;
+
+/// Error returned when serializing a message fails
+#[derive(Debug, Fail)]
+pub enum SerializationError {
+    #[fail(display = "message is invalid: {}", _0)]
+    Validation(#[cause] MessageValidationError),
+    #[fail(display = "serializing the message failed: {}", _0)]
+    Rcl(#[cause] RclError),
+}
+
+impl From<MessageValidationError> for SerializationError {
+    fn from(error: MessageValidationError) -> Self {
+        SerializationError::Validation(error)
+    }
+}
+
+impl From<RclError> for SerializationError {
+    fn from(error: RclError) -> Self {
+        SerializationError::Rcl(error)
+    }
+}
+
+/// Serializes a message the way the middleware would publish it, e.g. to CDR
+///
+/// Messages that fail [`rclrs_common::traits::Message::validate`] are refused with
+/// [`SerializationError::Validation`], as when publishing them.
+///
+/// ```ignore
+/// let buffer = serialize(&std_msgs::msg::String { data: "hello".to_owned() })?;
+/// let message: std_msgs::msg::String = deserialize(&buffer)?;
+/// ```
+pub fn serialize<T>(message: &T) -> Result<Vec<u8>, SerializationError>
+where
+    T: MessageDefinition<T>,
+{
+    message.validate()?;
+
+    let allocator = DefaultAllocator;
+    let mut serialized_message = unsafe { rcutils_get_zero_initialized_uint8_array() };
+    unsafe {
+        // The middleware resizes the buffer to fit the message
+        let rcl_allocator = allocator.rcl_allocator();
+        rcutils_uint8_array_init(
+            &mut serialized_message as *mut _,
+            0,
+            &rcl_allocator as *const _,
+        )
+        .ok()?;
+    }
+
+    let message_handle = T::static_get_native_message(message);
+    let result = unsafe {
+        rmw_serialize(
+            message_handle as *const c_void,
+            T::get_type_support() as *const rosidl_message_type_support_t,
+            &mut serialized_message as *mut _,
+        )
+    }
+    .ok()
+    .map(|_| {
+        if serialized_message.buffer_length == 0 {
+            return vec![];
+        }
+        unsafe {
+            std::slice::from_raw_parts(serialized_message.buffer, serialized_message.buffer_length)
+        }
+        .to_vec()
+    });
+    T::static_destroy_native_message(message_handle);
+
+    unsafe {
+        rcutils_uint8_array_fini(&mut serialized_message as *mut _).ok()?;
+    }
+
+    Ok(result?)
+}
+
+/// Deserializes a message from a buffer the middleware serialized, see [`serialize`]
+pub fn deserialize<T>(buffer: &[u8]) -> RclResult<T>
+where
+    T: MessageDefinition<T> + Default,
+{
+    let allocator = DefaultAllocator;
+    // Only read by rmw, so the buffer is handed over without copying
+    let serialized_message = rcl_serialized_message_t {
+        buffer: buffer.as_ptr() as *mut _,
+        buffer_length: buffer.len(),
+        buffer_capacity: buffer.len(),
+        allocator: allocator.rcl_allocator(),
+    };
+
+    let mut message = T::default();
+    let message_handle = message.get_native_message();
+    let result = unsafe {
+        rmw_deserialize(
+            &serialized_message as *const _,
+            T::get_type_support() as *const rosidl_message_type_support_t,
+            message_handle as *mut c_void,
+        )
+    }
+    .ok();
+    if result.is_ok() {
+        message.read_handle(message_handle);
+    }
+    message.destroy_native_message(message_handle);
+
+    result.map(|_| message)
+}
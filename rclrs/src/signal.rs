@@ -0,0 +1,37 @@
+use crate::error::{RclError, RclResult};
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Longest a spin blocks in `rcl_wait` without checking for a termination signal
+pub(crate) const SIGNAL_POLL_PERIOD: Duration = Duration::from_millis(100);
+
+/// Number of termination signals received, so that each context only acts on the signals
+/// received after it was initialized
+static TERMINATION_SIGNALS: AtomicUsize = AtomicUsize::new(0);
+
+// Only updates an atomic, which is all that is async-signal-safe here; the contexts that
+// opted in notice it and shut down outside of the handler
+extern "C" fn on_termination_signal(_signal: c_int) {
+    TERMINATION_SIGNALS.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Installs process-wide handlers for SIGINT and SIGTERM
+///
+/// The handlers replace any that were installed before, and stay installed for the rest of
+/// the process. Installing them again is harmless. Fails with [`RclError::Error`] if either
+/// can't be installed.
+pub(crate) fn install_termination_handlers() -> RclResult {
+    let handler = on_termination_signal as extern "C" fn(c_int);
+    for &signal in &[libc::SIGINT, libc::SIGTERM] {
+        if unsafe { libc::signal(signal, handler as libc::sighandler_t) } == libc::SIG_ERR {
+            return Err(RclError::Error);
+        }
+    }
+    Ok(())
+}
+
+/// Number of SIGINT and SIGTERM signals received since the handlers were installed
+pub(crate) fn termination_signal_count() -> usize {
+    TERMINATION_SIGNALS.load(Ordering::SeqCst)
+}
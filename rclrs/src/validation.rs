@@ -0,0 +1,179 @@
+//! Validation of node names, namespaces and topic names
+//!
+//! These only call into rmw and rcl's validation functions, which neither need an initialized
+//! [`crate::Context`] nor a loaded middleware, so names can be checked up front, e.g. those
+//! in a launch configuration.
+//!
+//! ```ignore
+//! validate_node_name("my_node")?;
+//! assert!(validate_namespace("/my/ns/").is_err());
+//! ```
+
+use crate::error::{RclError, ToRclResult};
+use failure::Fail;
+use rcl_sys::*;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+
+/// Error returned when validating a name fails
+#[derive(Debug, Fail)]
+pub enum NameValidationError {
+    /// The name breaks a naming rule, at the given byte index
+    #[fail(display = "`{}` is invalid at index {}: {}", name, index, reason)]
+    Invalid {
+        name: String,
+        reason: String,
+        /// Byte offset into `name`, as rmw validates bytes
+        ///
+        /// Names may only contain ASCII characters, so a multi-byte character is reported at
+        /// its first byte and `&name[index..]` always starts at the offending character.
+        index: usize,
+    },
+    #[fail(display = "validating a name failed: {}", _0)]
+    Rcl(#[cause] RclError),
+}
+
+impl From<RclError> for NameValidationError {
+    fn from(error: RclError) -> Self {
+        NameValidationError::Rcl(error)
+    }
+}
+
+type ValidateFn = unsafe extern "C" fn(*const c_char, *mut c_int, *mut usize) -> rcl_ret_t;
+type ValidationResultStringFn = unsafe extern "C" fn(c_int) -> *const c_char;
+
+/// Checks a node name, e.g. `"my_node"`, which may not contain slashes
+pub fn validate_node_name(name: &str) -> Result<(), NameValidationError> {
+    validate(
+        name,
+        RMW_NODE_NAME_VALID,
+        rmw_validate_node_name,
+        rmw_node_name_validation_result_string,
+    )
+}
+
+/// Checks an absolute namespace, e.g. `"/my/ns"`
+///
+/// The root namespace `"/"` is valid, an empty namespace is not.
+pub fn validate_namespace(namespace: &str) -> Result<(), NameValidationError> {
+    validate(
+        namespace,
+        RMW_NAMESPACE_VALID,
+        rmw_validate_namespace,
+        rmw_namespace_validation_result_string,
+    )
+}
+
+/// Checks a topic or service name before expansion, e.g. `"chatter"` or `"~/status"`
+pub fn validate_topic_name(name: &str) -> Result<(), NameValidationError> {
+    validate(
+        name,
+        RCL_TOPIC_NAME_VALID,
+        rcl_validate_topic_name,
+        rcl_topic_name_validation_result_string,
+    )
+}
+
+/// Checks a fully qualified topic or service name, e.g. `"/my/ns/chatter"`
+pub fn validate_full_topic_name(name: &str) -> Result<(), NameValidationError> {
+    validate(
+        name,
+        RMW_TOPIC_VALID,
+        rmw_validate_full_topic_name,
+        rmw_full_topic_name_validation_result_string,
+    )
+}
+
+fn validate(
+    name: &str,
+    valid: u32,
+    validate_fn: ValidateFn,
+    result_string_fn: ValidationResultStringFn,
+) -> Result<(), NameValidationError> {
+    let c_name = CString::new(name).map_err(|error| NameValidationError::Invalid {
+        name: name.to_owned(),
+        reason: "name must not contain null bytes".to_owned(),
+        index: error.nul_position(),
+    })?;
+
+    let mut validation_result: c_int = 0;
+    let mut invalid_index: usize = 0;
+    unsafe {
+        validate_fn(
+            c_name.as_ptr(),
+            &mut validation_result as *mut _,
+            &mut invalid_index as *mut _,
+        )
+        .ok()?;
+    }
+    if validation_result == valid as c_int {
+        return Ok(());
+    }
+
+    let reason = unsafe { result_string_fn(validation_result) };
+    let reason = if reason.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(reason) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    Err(NameValidationError::Invalid {
+        name: name.to_owned(),
+        reason,
+        index: invalid_index,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_index(result: Result<(), NameValidationError>) -> usize {
+        match result {
+            Err(NameValidationError::Invalid { index, .. }) => index,
+            other => panic!("expected an invalid name, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn valid_names_pass() {
+        assert!(validate_node_name("my_node").is_ok());
+        assert!(validate_namespace("/my/ns").is_ok());
+        assert!(validate_topic_name("chatter").is_ok());
+        assert!(validate_topic_name("~/status").is_ok());
+        assert!(validate_full_topic_name("/my/ns/chatter").is_ok());
+    }
+
+    #[test]
+    fn invalid_names_report_where() {
+        assert_eq!(invalid_index(validate_node_name("my/node")), 2);
+        assert_eq!(invalid_index(validate_namespace("/my/ns/")), 6);
+        assert_eq!(invalid_index(validate_full_topic_name("chatter")), 0);
+        assert!(validate_namespace("").is_err());
+    }
+
+    #[test]
+    fn null_bytes_are_refused() {
+        assert_eq!(invalid_index(validate_node_name("my\0node")), 2);
+    }
+
+    #[test]
+    fn root_namespace_is_valid() {
+        assert!(validate_namespace("/").is_ok());
+    }
+
+    #[test]
+    fn indices_are_byte_offsets_at_the_offending_character() {
+        let name = "é_node";
+        let index = invalid_index(validate_node_name(name));
+        assert_eq!(index, 0);
+        assert!(name[index..].starts_with('é'));
+
+        // A multi-byte character is invalid itself, so it's reported before a later slash
+        let name = "my_nodé/x";
+        let index = invalid_index(validate_node_name(name));
+        assert_eq!(index, 6);
+        assert!(name[index..].starts_with('é'));
+    }
+}
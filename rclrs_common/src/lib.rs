@@ -7,6 +7,18 @@ pub mod error {
         pub message: &'static str,
     }
 
+    /// A message field exceeds the bound declared for it in the message definition
+    #[derive(Debug, Fail, PartialEq, Eq)]
+    #[fail(
+        display = "field `{}` has length {}, but is bounded to {}",
+        field, length, bound
+    )]
+    pub struct MessageValidationError {
+        pub field: &'static str,
+        pub length: usize,
+        pub bound: usize,
+    }
+
     #[derive(Debug, Fail)]
     pub enum RCLStatusCode {
         #[fail(display = "success")]
@@ -39,6 +51,8 @@ pub mod error {
         NodeInvalidName = 201,
         #[fail(display = "invalid node namespace given")]
         NodeInvalidNamespace = 202,
+        #[fail(display = "failed to find node name")]
+        NodeNameNonExistent = 203,
         #[fail(display = "invalid publisher given")]
         PublisherInvalid = 300,
         #[fail(display = "invalid subscriber given")]
@@ -71,6 +85,14 @@ pub mod error {
         InvalidParamRule = 1010,
         #[fail(display = "argument is not a valid log level")]
         InvalidLogLevelRule = 1020,
+        #[fail(display = "invalid event given")]
+        EventInvalid = 2000,
+        #[fail(display = "failed to take an event from the event handle")]
+        EventTakeFailed = 2001,
+        #[fail(display = "lifecycle state already registered")]
+        LifecycleStateRegistered = 3000,
+        #[fail(display = "lifecycle state not registered")]
+        LifecycleStateNotRegistered = 3001,
     }
 
     impl From<i32> for RCLStatusCode {
@@ -91,6 +113,7 @@ pub mod error {
                 200 => RCLStatusCode::NodeInvalid,
                 201 => RCLStatusCode::NodeInvalidName,
                 202 => RCLStatusCode::NodeInvalidNamespace,
+                203 => RCLStatusCode::NodeNameNonExistent,
                 300 => RCLStatusCode::PublisherInvalid,
                 400 => RCLStatusCode::SubscriptionInvalid,
                 401 => RCLStatusCode::SubscriptionTakeFailed,
@@ -107,6 +130,10 @@ pub mod error {
                 1002 => RCLStatusCode::WrongLexeme,
                 1010 => RCLStatusCode::InvalidParamRule,
                 1020 => RCLStatusCode::InvalidLogLevelRule,
+                2000 => RCLStatusCode::EventInvalid,
+                2001 => RCLStatusCode::EventTakeFailed,
+                3000 => RCLStatusCode::LifecycleStateRegistered,
+                3001 => RCLStatusCode::LifecycleStateNotRegistered,
                 _ => unimplemented!(),
             }
         }
@@ -123,13 +150,29 @@ pub mod traits {
         fn get_native_message(&self) -> uintptr_t;
         fn destroy_native_message(&self, message_handle: uintptr_t);
         fn read_handle(&mut self, message_handle: uintptr_t);
+
+        /// Checks the bounds of bounded strings and sequences before the message is converted
+        fn validate(&self) -> Result<(), crate::error::MessageValidationError> {
+            Ok(())
+        }
     }
 
     downcast!(dyn Message);
 
     pub trait MessageDefinition<T>: Message {
+        /// ROS name of the message type, e.g. `"std_msgs/msg/String"`
+        const TYPE_NAME: &'static str;
+
         fn get_type_support() -> uintptr_t;
         fn static_get_native_message(message: &T) -> uintptr_t;
         fn static_destroy_native_message(message_handle: uintptr_t);
     }
+
+    /// A message with a header stamp, like the ones with a `std_msgs/Header`
+    ///
+    /// Implemented by the generated code, as the message crates only depend on this crate and
+    /// the stamp is therefore passed as nanoseconds instead of as an `rclrs::Time`.
+    pub trait Stamped {
+        fn set_stamp(&mut self, nanoseconds: i64);
+    }
 }
@@ -1,7 +1,7 @@
 use rclrs;
 use std_msgs;
 
-fn main() -> rclrs::RclResult {
+fn main() -> Result<(), rclrs::PublishError> {
     let context = rclrs::Context::default();
 
     let node = context.create_node("minimal_publisher")?;
@@ -4,7 +4,7 @@ use std_msgs;
 fn main() -> rclrs::RclResult {
     let context = rclrs::Context::default();
 
-    let mut node = context.create_node("minimal_subscriber")?;
+    let node = context.create_node("minimal_subscriber")?;
 
     let mut num_messages: usize = 0;
 
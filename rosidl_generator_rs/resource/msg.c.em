@@ -1,12 +1,34 @@
+#include <stdlib.h>
+#include <string.h>
+
+#include "rosidl_runtime_c/primitives_sequence_functions.h"
 #include "rosidl_runtime_c/string_functions.h"
+#include "rosidl_runtime_c/u16string_functions.h"
 #include "rosidl_runtime_c/message_type_support_struct.h"
 
 @{
 from rosidl_parser.definition import AbstractGenericString
 from rosidl_parser.definition import AbstractNestedType
+from rosidl_parser.definition import AbstractSequence
+from rosidl_parser.definition import AbstractWString
 from rosidl_parser.definition import Array
 from rosidl_parser.definition import BasicType
+from rosidl_parser.definition import NamespacedType
+
+# Nested messages may come from other packages, their functions are declared in their headers
+nested_headers = []
+for _, msg_spec in msg_specs:
+    for member in msg_spec.structure.members:
+        if isinstance(member.type, NamespacedType):
+            header = '%s/%s.h' % (
+                '/'.join(member.type.namespaces),
+                convert_camel_case_to_lower_case_underscore(member.type.name))
+            if header not in nested_headers:
+                nested_headers.append(header)
 }@
+@[for header in nested_headers]@
+#include "@(header)"
+@[end for]@
 
 @[for subfolder, msg_spec in msg_specs]@
 @{
@@ -14,27 +36,93 @@ type_name = msg_spec.structure.namespaced_type.name
 c_fields = []
 for member in msg_spec.structure.members:
     if type(member.type) is Array:
-        pass
+        if isinstance(member.type.value_type, BasicType):
+            c_fields.append("%s %s" % (get_c_type(member.type), member.name))
     else:
-        if isinstance(member.type, BasicType) or isinstance(member.type, AbstractGenericString):
+        if isinstance(member.type, AbstractWString):
+            c_fields.append("%s %s" % (get_c_type(member.type), member.name))
+            c_fields.append("size_t %s_size" % member.name)
+        elif isinstance(member.type, BasicType) or isinstance(member.type, AbstractGenericString):
             c_fields.append("%s %s" % (get_c_type(member.type), member.name))
+        elif isinstance(member.type, NamespacedType):
+            c_fields.append("uintptr_t %s" % member.name)
+        elif isinstance(member.type, AbstractSequence) and isinstance(member.type.value_type, BasicType):
+            c_fields.append("%s %s" % (get_c_type(member.type), member.name))
+            c_fields.append("size_t %s_size" % member.name)
         else:
             pass
 
 msg_normalized_type = get_rs_type(msg_spec.structure.namespaced_type).replace('::', '__')
+nested_members = [
+    (member, get_rs_type(member.type).replace('::', '__'))
+    for member in msg_spec.structure.members if isinstance(member.type, NamespacedType)]
 }@
 
 #include "@(package_name)/@(subfolder)/@(convert_camel_case_to_lower_case_underscore(type_name)).h"
 
+@[for member in msg_spec.structure.members]@
+@[    if isinstance(member.type, Array) and isinstance(member.type.value_type, BasicType)]@
+// The Rust side copies exactly this many elements of this size
+_Static_assert(
+  sizeof(((@(msg_normalized_type) *)0)->@(member.name)) == @(member.type.size) * sizeof(@(get_c_type(member.type.value_type))),
+  "unexpected layout of @(type_name).@(member.name)");
+@[    end if]@
+@[end for]@
+
 uintptr_t @(package_name)_msg_@(convert_camel_case_to_lower_case_underscore(type_name))_get_type_support() {
     return (uintptr_t)ROSIDL_GET_MSG_TYPE_SUPPORT(@(package_name), @(subfolder), @(msg_spec.structure.namespaced_type.name));
 }
 
 uintptr_t @(package_name)_msg_@(convert_camel_case_to_lower_case_underscore(type_name))_get_native_message(
   @(', '.join(c_fields))) {
+@[if nested_members]@
+      // A nested conversion that failed is passed in as 0. The ones that succeeded are owned
+      // here until they are moved in, so they have to be freed if anything fails before that.
+      @(msg_normalized_type) *ros_message = NULL;
+      if (@(' && '.join('%s != 0' % member.name for member, _ in nested_members))) {
+        ros_message = @(msg_normalized_type)__create();
+      }
+      if (!ros_message) {
+@[  for member, nested_normalized_type in nested_members]@
+        if (@(member.name) != 0) {
+          @(nested_normalized_type)__destroy((@(nested_normalized_type) *)@(member.name));
+        }
+@[  end for]@
+        return 0;
+      }
+@[  for member, nested_normalized_type in nested_members]@
+      {
+        // Moves the nested message in, together with the strings and sequences it owns.
+        // Its shell was allocated by `__create`, which uses malloc.
+        @(nested_normalized_type) * nested_message = (@(nested_normalized_type) *)@(member.name);
+        @(nested_normalized_type)__fini(&ros_message->@(member.name));
+        ros_message->@(member.name) = *nested_message;
+        free(nested_message);
+      }
+@[  end for]@
+@[else]@
       @(msg_normalized_type) *ros_message = @(msg_normalized_type)__create();
+      if (!ros_message) {
+        return 0;
+      }
+@[end if]@
 @[for member in msg_spec.structure.members]@
-@[    if isinstance(member.type, Array)]@
+@[    if isinstance(member.type, Array) and isinstance(member.type.value_type, BasicType)]@
+      memcpy(ros_message->@(member.name), @(member.name), sizeof(ros_message->@(member.name)));
+@[    elif isinstance(member.type, Array)]@
+@[    elif isinstance(member.type, AbstractSequence) and isinstance(member.type.value_type, BasicType)]@
+      if (!@(get_c_sequence_type(member.type))__init(&ros_message->@(member.name), @(member.name)_size)) {
+        @(msg_normalized_type)__destroy(ros_message);
+        return 0;
+      }
+      if (@(member.name)_size > 0) {
+        memcpy(ros_message->@(member.name).data, @(member.name), @(member.name)_size * sizeof(*@(member.name)));
+      }
+@[    elif isinstance(member.type, AbstractWString)]@
+      if (!rosidl_runtime_c__U16String__assignn(&ros_message->@(member.name), @(member.name), @(member.name)_size)) {
+        @(msg_normalized_type)__destroy(ros_message);
+        return 0;
+      }
 @[    elif isinstance(member.type, AbstractGenericString)]@
       rosidl_runtime_c__String__assign(&(ros_message->@(member.name)), @(member.name));
 @[    elif isinstance(member.type, BasicType)]@
@@ -51,7 +139,10 @@ void @(package_name)_msg_@(convert_camel_case_to_lower_case_underscore(type_name
 
 @[for member in msg_spec.structure.members]@
 @(get_c_type(member.type)) @(package_name)_msg_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(uintptr_t message_handle) {
-@[    if isinstance(member.type, Array)]@
+@[    if isinstance(member.type, Array) and isinstance(member.type.value_type, BasicType)]@
+    @(msg_normalized_type) * ros_message = (@(msg_normalized_type) *)message_handle;
+    return ros_message->@(member.name);
+@[    elif isinstance(member.type, Array)]@
     (void)message_handle;
     return 0;
 @[    elif isinstance(member.type, AbstractGenericString)]@
@@ -60,6 +151,12 @@ void @(package_name)_msg_@(convert_camel_case_to_lower_case_underscore(type_name
 @[    elif isinstance(member.type, BasicType)]@
     @(msg_normalized_type) * ros_message = (@(msg_normalized_type) *)message_handle;
     return ros_message->@(member.name);
+@[    elif isinstance(member.type, NamespacedType)]@
+    @(msg_normalized_type) * ros_message = (@(msg_normalized_type) *)message_handle;
+    return (uintptr_t)&ros_message->@(member.name);
+@[    elif isinstance(member.type, AbstractSequence) and isinstance(member.type.value_type, BasicType)]@
+    @(msg_normalized_type) * ros_message = (@(msg_normalized_type) *)message_handle;
+    return ros_message->@(member.name).data;
 @[    elif isinstance(member.type, AbstractNestedType)]@
     @(msg_normalized_type) * ros_message = (@(msg_normalized_type) *)message_handle;
     return (@(get_c_type(member.type)))&ros_message->@(member.name);
@@ -68,6 +165,13 @@ void @(package_name)_msg_@(convert_camel_case_to_lower_case_underscore(type_name
     return 0;
 @[    end if]@
 }
+@[    if isinstance(member.type, AbstractWString) or (isinstance(member.type, AbstractSequence) and isinstance(member.type.value_type, BasicType))]@
+
+size_t @(package_name)_msg_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_size(uintptr_t message_handle) {
+    @(msg_normalized_type) * ros_message = (@(msg_normalized_type) *)message_handle;
+    return ros_message->@(member.name).size;
+}
+@[    end if]@
 @[end for]@
 
 @[end for]
@@ -8,8 +8,13 @@ use std::ffi::CStr;
 from rosidl_parser.definition import AbstractGenericString
 from rosidl_parser.definition import AbstractNestedType
 from rosidl_parser.definition import AbstractSequence
+from rosidl_parser.definition import AbstractWString
 from rosidl_parser.definition import BasicType
+from rosidl_parser.definition import BoundedSequence
+from rosidl_parser.definition import BoundedString
+from rosidl_parser.definition import BoundedWString
 from rosidl_parser.definition import Array
+from rosidl_parser.definition import NamespacedType
 }@
 
 @[for subfolder, msg_spec in msg_specs]@
@@ -17,23 +22,50 @@ from rosidl_parser.definition import Array
 type_name = msg_spec.structure.namespaced_type.name
 }@
 
-#[derive(Default)]
 pub struct @(type_name) {
 @[for member in msg_spec.structure.members]@
     pub @(get_rs_name(member.name)): @(get_rs_type(member.type).replace(package_name, 'crate')),
 @[end for]@
 }
 
+// Not derived, arrays only implement Default up to a length of 32
+impl Default for @(type_name) {
+  fn default() -> Self {
+    Self {
+@[for member in msg_spec.structure.members]@
+@[    if isinstance(member.type, Array) and isinstance(member.type.value_type, BasicType)]@
+      @(get_rs_name(member.name)): [Default::default(); @(member.type.size)],
+@[    elif isinstance(member.type, Array)]@
+      // Strings and messages aren't Copy, so every element is listed
+      @(get_rs_name(member.name)): [@(', '.join(['Default::default()'] * member.type.size))],
+@[    else]@
+      @(get_rs_name(member.name)): Default::default(),
+@[    end if]@
+@[end for]@
+    }
+  }
+}
+
 #[link(name = "@(package_name)__rosidl_typesupport_c__rsext")]
 extern "C" {
     fn @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_get_type_support() -> uintptr_t;
 
     fn @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_get_native_message(
 @[for member in msg_spec.structure.members]@
-@[    if isinstance(member.type, AbstractGenericString)]@
+@[    if isinstance(member.type, Array) and isinstance(member.type.value_type, BasicType)]@
+    @(get_rs_name(member.name)): *const @(get_rs_type(member.type.value_type)),
+@[    elif isinstance(member.type, AbstractWString)]@
+    @(get_rs_name(member.name)): *const u16,
+    @(member.name)_size: usize,
+@[    elif isinstance(member.type, AbstractGenericString)]@
     @(get_rs_name(member.name)): *const c_char,
 @[    elif isinstance(member.type, BasicType)]@
     @(get_rs_name(member.name)): @(get_rs_type(member.type)),
+@[    elif isinstance(member.type, NamespacedType)]@
+    @(get_rs_name(member.name)): uintptr_t,
+@[    elif isinstance(member.type, AbstractSequence) and isinstance(member.type.value_type, BasicType)]@
+    @(get_rs_name(member.name)): *const @(get_rs_type(member.type.value_type)),
+    @(member.name)_size: usize,
 @[    end if]@
 @[end for]@
     ) -> uintptr_t;
@@ -41,24 +73,50 @@ extern "C" {
     fn @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_destroy_native_message(message_handle: uintptr_t) -> ();
 
 @[for member in msg_spec.structure.members]@
-@[    if isinstance(member.type, Array)]@
+@[    if isinstance(member.type, Array) and isinstance(member.type.value_type, BasicType)]@
+    fn @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(message_handle: uintptr_t) -> *const @(get_rs_type(member.type.value_type));
+@[    elif isinstance(member.type, Array)]@
+@[    elif isinstance(member.type, AbstractWString)]@
+    fn @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(message_handle: uintptr_t) -> *const u16;
+    fn @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_size(message_handle: uintptr_t) -> usize;
 @[    elif isinstance(member.type, AbstractGenericString)]@
     fn @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(message_handle: uintptr_t) -> *const c_char;
 @[    elif isinstance(member.type, BasicType)]@
     fn @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(message_handle: uintptr_t) -> @(get_rs_type(member.type));
+@[    elif isinstance(member.type, NamespacedType)]@
+    fn @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(message_handle: uintptr_t) -> uintptr_t;
+@[    elif isinstance(member.type, AbstractSequence) and isinstance(member.type.value_type, BasicType)]@
+    fn @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(message_handle: uintptr_t) -> *const @(get_rs_type(member.type.value_type));
+    fn @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_size(message_handle: uintptr_t) -> usize;
 @[    end if]@
 @[end for]@
 }
 
 impl @(type_name) {
   fn get_native_message(&self) -> uintptr_t {
+@[for member in msg_spec.structure.members]@
+@[    if isinstance(member.type, AbstractWString)]@
+    let @(member.name)_utf16: Vec<u16> = self.@(get_rs_name(member.name)).encode_utf16().collect();
+@[    end if]@
+@[end for]@
     return unsafe { @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_get_native_message(
 @[for member in msg_spec.structure.members]@
-@[    if isinstance(member.type, Array)]@
+@[    if isinstance(member.type, Array) and isinstance(member.type.value_type, BasicType)]@
+    self.@(get_rs_name(member.name)).as_ptr(),
+@[    elif isinstance(member.type, Array)]@
+@[    elif isinstance(member.type, AbstractWString)]@
+    @(member.name)_utf16.as_ptr(),
+    @(member.name)_utf16.len(),
 @[    elif isinstance(member.type, AbstractGenericString)]@
     CString::new(self.@(get_rs_name(member.name)).clone()).unwrap().as_ptr(),
 @[    elif isinstance(member.type, BasicType)]@
     self.@(get_rs_name(member.name)),
+@[    elif isinstance(member.type, NamespacedType)]@
+    // Ownership of the nested native message passes to the outer one
+    rclrs_common::traits::Message::get_native_message(&self.@(get_rs_name(member.name))),
+@[    elif isinstance(member.type, AbstractSequence) and isinstance(member.type.value_type, BasicType)]@
+    self.@(get_rs_name(member.name)).as_ptr(),
+    self.@(get_rs_name(member.name)).len(),
 @[    end if]@
 @[end for]@
     ) };
@@ -75,12 +133,39 @@ impl @(type_name) {
     unsafe {
       {
 @[for member in msg_spec.structure.members]@
-@[    if isinstance(member.type, Array)]@
+@[    if isinstance(member.type, Array) and isinstance(member.type.value_type, BasicType)]@
+      let ptr = @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(_message_handle);
+      self.@(get_rs_name(member.name)).copy_from_slice(std::slice::from_raw_parts(ptr, @(member.type.size)));
+@[    elif isinstance(member.type, Array)]@
+@[    elif isinstance(member.type, AbstractWString)]@
+      let ptr = @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(_message_handle);
+      let size = @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_size(_message_handle);
+      // Unpaired surrogates can't be represented in a String and are replaced
+      self.@(get_rs_name(member.name)).clear();
+      if size > 0 {
+        self.@(get_rs_name(member.name)).extend(
+          std::char::decode_utf16(std::slice::from_raw_parts(ptr, size).iter().cloned())
+            .map(|c| c.unwrap_or(std::char::REPLACEMENT_CHARACTER)));
+      }
 @[    elif isinstance(member.type, AbstractGenericString)]@
       let ptr = @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(_message_handle);
-      self.@(get_rs_name(member.name)) = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+      // Keeps the storage of the string, for taking into the same message repeatedly
+      self.@(get_rs_name(member.name)).clear();
+      self.@(get_rs_name(member.name)).push_str(&CStr::from_ptr(ptr).to_string_lossy());
 @[    elif isinstance(member.type, BasicType)]@
       self.@(get_rs_name(member.name)) = @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(_message_handle);
+@[    elif isinstance(member.type, NamespacedType)]@
+      let handle = @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(_message_handle);
+      rclrs_common::traits::Message::read_handle(&mut self.@(get_rs_name(member.name)), handle);
+@[    elif isinstance(member.type, AbstractSequence) and isinstance(member.type.value_type, BasicType)]@
+      let ptr = @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_read_handle(_message_handle);
+      let size = @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_@(member.name)_size(_message_handle);
+      // Keeps the storage of the Vec, like for strings. An empty sequence may not have any
+      // storage at all.
+      self.@(get_rs_name(member.name)).clear();
+      if size > 0 {
+        self.@(get_rs_name(member.name)).extend_from_slice(std::slice::from_raw_parts(ptr, size));
+      }
 @[    elif isinstance(member.type, AbstractSequence)]@
 @[    end if]@
 @[end for]@
@@ -101,9 +186,34 @@ impl rclrs_common::traits::Message for @(type_name) {
   fn read_handle(&mut self, message_handle: uintptr_t) -> () {
     self.read_handle(message_handle);
   }
+
+  fn validate(&self) -> Result<(), rclrs_common::error::MessageValidationError> {
+@[for member in msg_spec.structure.members]@
+@[    if isinstance(member.type, (BoundedString, BoundedSequence, BoundedWString))]@
+@{
+if isinstance(member.type, BoundedWString):
+    length = 'self.%s.encode_utf16().count()' % get_rs_name(member.name)
+else:
+    length = 'self.%s.len()' % get_rs_name(member.name)
+}@
+    if @(length) > @(member.type.maximum_size) {
+      return Err(rclrs_common::error::MessageValidationError {
+        field: "@(member.name)",
+        length: @(length),
+        bound: @(member.type.maximum_size),
+      });
+    }
+@[    elif isinstance(member.type, NamespacedType)]@
+    rclrs_common::traits::Message::validate(&self.@(get_rs_name(member.name)))?;
+@[    end if]@
+@[end for]@
+    Ok(())
+  }
 }
 
 impl rclrs_common::traits::MessageDefinition<@(type_name)> for @(type_name) {
+  const TYPE_NAME: &'static str = "@(package_name)/@(subfolder)/@(type_name)";
+
   fn get_type_support() -> uintptr_t {
     return unsafe { @(package_name)_@(subfolder)_@(convert_camel_case_to_lower_case_underscore(type_name))_get_type_support() };
   }
@@ -119,4 +229,23 @@ impl rclrs_common::traits::MessageDefinition<@(type_name)> for @(type_name) {
   }
 }
 
+@{
+stamp_field = None
+for member in msg_spec.structure.members:
+    if isinstance(member.type, NamespacedType):
+        if member.name == 'header' and member.type.namespaced_name() == ('std_msgs', 'msg', 'Header'):
+            stamp_field = 'header.stamp'
+        elif member.name == 'stamp' and member.type.namespaced_name() == ('builtin_interfaces', 'msg', 'Time'):
+            stamp_field = 'stamp'
+}@
+@[if stamp_field]@
+impl rclrs_common::traits::Stamped for @(type_name) {
+  fn set_stamp(&mut self, nanoseconds: i64) {
+    // builtin_interfaces/Time keeps a non-negative nanosecond part, also before the epoch
+    self.@(stamp_field).sec = nanoseconds.div_euclid(1_000_000_000) as i32;
+    self.@(stamp_field).nanosec = nanoseconds.rem_euclid(1_000_000_000) as u32;
+  }
+}
+
+@[end if]@
 @[end for]
@@ -24,6 +24,7 @@ from rosidl_cmake import read_generator_arguments
 from rosidl_parser.definition import AbstractGenericString
 from rosidl_parser.definition import AbstractNestedType
 from rosidl_parser.definition import AbstractSequence
+from rosidl_parser.definition import AbstractWString
 from rosidl_parser.definition import BoundedSequence
 from rosidl_parser.definition import Array
 from rosidl_parser.definition import BasicType
@@ -89,6 +90,7 @@ def generate_rs(generator_arguments_file, typesupport_impls):
 
     data = {
         'get_c_type': get_c_type,
+        'get_c_sequence_type': get_c_sequence_type,
         'get_rs_type': get_rs_type,
         'get_rs_name': get_rs_name,
         'constant_value_to_rs': constant_value_to_rs,
@@ -269,7 +271,7 @@ def get_builtin_rs_type(type_, package_name=None):
     elif isinstance(type_, AbstractGenericString):
         return 'std::string::String'
     elif isinstance(type_, Array):
-        return '[{}; {}]'.format(get_rs_type(type_.value_type), 32 if type_.size <= 32 else 32)
+        return '[{}; {}]'.format(get_rs_type(type_.value_type), type_.size)
     elif isinstance(type_, AbstractSequence):
         return 'Vec<{}>'.format(get_rs_type(type_.value_type))
 
@@ -312,13 +314,25 @@ def get_builtin_c_type(type_):
             return 'int64_t'
         if type_.typename == 'uint64':
             return 'uint64_t'
+    elif isinstance(type_, AbstractWString):
+        # Passed as UTF-16 code units, like a sequence, see rosidl_runtime_c/u16string.h
+        return 'const uint16_t *'
     elif isinstance(type_, AbstractGenericString):
         return 'const char *'
 
     assert False, "unknown type '%s'" % type_.typename
 
 
+def get_c_sequence_type(type_):
+    # e.g. rosidl_runtime_c__int32__Sequence, see rosidl_runtime_c/primitives_sequence.h
+    return 'rosidl_runtime_c__%s__Sequence' % type_.value_type.typename.replace(' ', '_')
+
+
 def get_c_type(type_, subfolder='msg'):
+    # Arrays and sequences of basic types are passed as a pointer to their first element
+    if isinstance(type_, (Array, AbstractSequence)) and isinstance(type_.value_type, BasicType):
+        return 'const %s *' % get_builtin_c_type(type_.value_type)
+
     if not isinstance(type_, BasicType) and not isinstance(type_, AbstractGenericString):
         return 'uintptr_t'
 